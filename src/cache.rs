@@ -0,0 +1,429 @@
+use std::{
+    collections::HashMap,
+    future::Future,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::SystemTime,
+};
+use tokio::sync::Mutex;
+use tokio::task::spawn_blocking;
+
+/// Shared download cache directory, kept across restarts
+pub const CACHE_DIR: &str = "/tmp/music_bot_downloads/cache";
+
+/// Default total cache size when `CACHE_MAX_MB` is not set
+const DEFAULT_QUOTA_MB: u64 = 2048;
+
+/// Subdirectory of the cache holding the measured loudness of each file,
+/// as one `<key>` file containing the value in LUFS
+const LOUDNESS_DIR: &str = "loudness";
+
+type DownloadError = Box<dyn std::error::Error + Send + Sync>;
+
+struct CacheEntry {
+    path: PathBuf,
+    size: u64,
+    /// Number of queued/playing tracks currently using the file
+    refs: usize,
+    last_used: SystemTime,
+    /// Integrated loudness in LUFS, measured once per file
+    loudness: Option<f64>,
+    /// Failed to play, deleted as soon as nothing uses it
+    stale: bool,
+}
+
+#[derive(Default)]
+struct CacheIndex {
+    entries: HashMap<String, CacheEntry>,
+    /// Per-key locks so two guilds asking for the same video download it once
+    in_flight: HashMap<String, Arc<Mutex<()>>>,
+}
+
+/// Content-addressed download cache shared by all guilds.
+///
+/// Files are stored as `<key>.<ext>` where the key is derived from the source
+/// video ID (see `cache_key`). Every track using a file holds a reference
+/// (`fetch` takes one, `release` drops it). Unreferenced files stay on disk for
+/// replays until the total size exceeds the quota, then the least recently used
+/// ones are evicted.
+#[derive(Clone)]
+pub struct DownloadCache {
+    dir: PathBuf,
+    quota_bytes: u64,
+    index: Arc<Mutex<CacheIndex>>,
+}
+
+impl DownloadCache {
+    /// Open the cache directory and index the files left by a previous run.
+    /// The quota is read from `CACHE_MAX_MB`.
+    pub fn open() -> Self {
+        let quota_mb = std::env::var("CACHE_MAX_MB")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(DEFAULT_QUOTA_MB);
+
+        let dir = PathBuf::from(CACHE_DIR);
+        let _ = std::fs::create_dir_all(&dir);
+
+        let mut index = CacheIndex::default();
+        if let Ok(entries) = std::fs::read_dir(&dir) {
+            for entry in entries.flatten() {
+                let path = entry.path();
+                let Ok(meta) = entry.metadata() else { continue };
+                if !meta.is_file() {
+                    continue;
+                }
+
+                // Partial downloads from a crash are useless, drop them
+                let is_partial = path
+                    .extension()
+                    .is_some_and(|ext| ext == "part" || ext == "ytdl");
+                let key = path.file_stem().and_then(|s| s.to_str()).map(str::to_string);
+                match key {
+                    Some(key) if !is_partial => {
+                        let loudness = std::fs::read_to_string(dir.join(LOUDNESS_DIR).join(&key))
+                            .ok()
+                            .and_then(|value| value.trim().parse().ok());
+                        index.entries.insert(
+                            key,
+                            CacheEntry {
+                                path: path.clone(),
+                                size: meta.len(),
+                                refs: 0,
+                                last_used: meta.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+                                loudness,
+                                stale: false,
+                            },
+                        );
+                    }
+                    _ => {
+                        let _ = std::fs::remove_file(&path);
+                    }
+                }
+            }
+        }
+
+        println!(
+            "💾 Download cache: {} file(s), quota {} MB",
+            index.entries.len(),
+            quota_mb
+        );
+
+        Self {
+            dir,
+            quota_bytes: quota_mb * 1024 * 1024,
+            index: Arc::new(Mutex::new(index)),
+        }
+    }
+
+    /// Total size the cache may grow to, also the largest file worth downloading
+    pub fn quota_bytes(&self) -> u64 {
+        self.quota_bytes
+    }
+
+    /// Get the cached file for `key`, downloading it with `download` on a miss.
+    /// Takes a reference on the file, which must be given back with `release`.
+    pub async fn fetch<F, Fut>(&self, key: &str, download: F) -> Result<PathBuf, DownloadError>
+    where
+        F: FnOnce(PathBuf) -> Fut,
+        Fut: Future<Output = Result<PathBuf, DownloadError>>,
+    {
+        let key_lock = self.key_lock(key).await;
+        let guard = key_lock.lock().await;
+
+        let result = match self.acquire(key).await {
+            Some(path) => Ok(path),
+            None => match download(self.dir.clone()).await {
+                Ok(path) => {
+                    let size = tokio::fs::metadata(&path).await.map(|m| m.len()).unwrap_or(0);
+                    self.index.lock().await.entries.insert(
+                        key.to_string(),
+                        CacheEntry {
+                            path: path.clone(),
+                            size,
+                            refs: 1,
+                            last_used: SystemTime::now(),
+                            loudness: None,
+                            stale: false,
+                        },
+                    );
+                    Ok(path)
+                }
+                Err(e) => Err(e),
+            },
+        };
+
+        drop(guard);
+        self.forget_key_lock(key, &key_lock).await;
+
+        self.evict().await;
+        result
+    }
+
+    /// Lock held while a key's file is downloaded or deleted
+    async fn key_lock(&self, key: &str) -> Arc<Mutex<()>> {
+        let mut index = self.index.lock().await;
+        index.in_flight.entry(key.to_string()).or_default().clone()
+    }
+
+    /// Forget a key lock once nobody else is waiting on it
+    async fn forget_key_lock(&self, key: &str, key_lock: &Arc<Mutex<()>>) {
+        let mut index = self.index.lock().await;
+        if Arc::strong_count(key_lock) <= 2 {
+            index.in_flight.remove(key);
+        }
+    }
+
+    /// Take a reference on an already cached file
+    pub async fn acquire(&self, key: &str) -> Option<PathBuf> {
+        let mut index = self.index.lock().await;
+        let entry = index.entries.get_mut(key)?;
+
+        if !entry.path.is_file() {
+            // Deleted behind our back
+            index.entries.remove(key);
+            return None;
+        }
+
+        entry.refs += 1;
+        entry.last_used = SystemTime::now();
+        Some(entry.path.clone())
+    }
+
+    /// Give back a reference taken by `fetch` or `acquire`
+    pub async fn release(&self, key: &str) {
+        {
+            let mut index = self.index.lock().await;
+            if let Some(entry) = index.entries.get_mut(key) {
+                entry.refs = entry.refs.saturating_sub(1);
+                entry.last_used = SystemTime::now();
+            }
+        }
+        self.evict().await;
+    }
+
+    /// Mark a cached file that failed to play. It is deleted once the last
+    /// reference is released, so a later fetch downloads it again, while
+    /// others who may be playing it fine keep it until then.
+    pub async fn invalidate(&self, key: &str) {
+        match self.index.lock().await.entries.get_mut(key) {
+            Some(entry) => entry.stale = true,
+            None => return,
+        }
+        self.evict().await;
+    }
+
+    /// Loudness of a cached file in LUFS. The first caller analyses it with
+    /// `measure` under the key's lock, so guilds asking at once share one
+    /// measurement and the file can't be evicted meanwhile.
+    pub async fn loudness<F, Fut>(&self, key: &str, measure: F) -> Result<f64, DownloadError>
+    where
+        F: FnOnce(PathBuf) -> Fut,
+        Fut: Future<Output = Result<f64, DownloadError>>,
+    {
+        let key_lock = self.key_lock(key).await;
+        let guard = key_lock.lock().await;
+
+        let known = {
+            let index = self.index.lock().await;
+            index
+                .entries
+                .get(key)
+                .map(|entry| (entry.path.clone(), entry.loudness))
+        };
+        let result = match known {
+            Some((_, Some(lufs))) => Ok(lufs),
+            Some((path, None)) => match measure(path).await {
+                Ok(lufs) => {
+                    self.set_loudness(key, lufs).await;
+                    Ok(lufs)
+                }
+                Err(e) => Err(e),
+            },
+            None => Err(format!("{} is not cached", key).into()),
+        };
+
+        drop(guard);
+        self.forget_key_lock(key, &key_lock).await;
+        result
+    }
+
+    /// Store the measured loudness of a cached file, on disk too so replays
+    /// after a restart need no re-analysis
+    async fn set_loudness(&self, key: &str, lufs: f64) {
+        match self.index.lock().await.entries.get_mut(key) {
+            Some(entry) => entry.loudness = Some(lufs),
+            None => return,
+        }
+
+        let dir = self.dir.join(LOUDNESS_DIR);
+        let saved = async {
+            tokio::fs::create_dir_all(&dir).await?;
+            tokio::fs::write(dir.join(key), lufs.to_string()).await
+        };
+        if let Err(e) = saved.await {
+            println!("⚠️ Cache: Failed to save loudness of {}: {}", key, e);
+        }
+    }
+
+    /// Delete unreferenced stale files, then least recently used, unreferenced
+    /// files until the cache fits its quota
+    pub async fn evict(&self) {
+        let evicted: Vec<String> = {
+            let index = self.index.lock().await;
+            let mut total: u64 = index.entries.values().map(|e| e.size).sum();
+
+            let mut candidates: Vec<(&String, bool, SystemTime, u64)> = index
+                .entries
+                .iter()
+                .filter(|(_, e)| e.refs == 0)
+                .map(|(key, e)| (key, e.stale, e.last_used, e.size))
+                .collect();
+            candidates.sort_by_key(|(_, stale, last_used, _)| (!*stale, *last_used));
+
+            let mut evicted = Vec::new();
+            for (key, stale, _, size) in candidates {
+                if !stale && total <= self.quota_bytes {
+                    break;
+                }
+                evicted.push(key.clone());
+                total -= size;
+            }
+            evicted
+        };
+
+        let mut count = 0;
+        for key in evicted {
+            // Delete under the key's lock so a fetch of it waits and downloads
+            // it again instead of finding the file gone. Skip keys being fetched.
+            let key_lock = self.key_lock(&key).await;
+            if let Ok(_guard) = key_lock.try_lock() {
+                if self.remove_unused(&key).await {
+                    count += 1;
+                }
+            }
+            self.forget_key_lock(&key, &key_lock).await;
+        }
+        if count > 0 {
+            println!("🧹 Cache: Evicted {} file(s)", count);
+        }
+    }
+
+    /// Drop an unreferenced key from the index and delete its files. Returns
+    /// whether it was removed, files that got a reference meanwhile are kept.
+    async fn remove_unused(&self, key: &str) -> bool {
+        let path = {
+            let mut index = self.index.lock().await;
+            match index.entries.get(key) {
+                Some(entry) if entry.refs == 0 => index.entries.remove(key).map(|e| e.path),
+                _ => None,
+            }
+        };
+        let Some(path) = path else {
+            return false;
+        };
+
+        let loudness = self.dir.join(LOUDNESS_DIR).join(key);
+        let _ = spawn_blocking(move || {
+            let _ = std::fs::remove_file(path);
+            let _ = std::fs::remove_file(loudness);
+        })
+        .await;
+        true
+    }
+
+    /// Remove files in the cache directory that are not indexed and not being
+    /// downloaded (e.g. leftovers of failed downloads), then enforce the quota
+    pub async fn sweep(&self) {
+        let (known, known_keys, in_flight): (Vec<PathBuf>, Vec<String>, Vec<String>) = {
+            let index = self.index.lock().await;
+            (
+                index.entries.values().map(|e| e.path.clone()).collect(),
+                index.entries.keys().cloned().collect(),
+                index.in_flight.keys().cloned().collect(),
+            )
+        };
+
+        let dir = self.dir.clone();
+        let cleanup_result = spawn_blocking(move || {
+            let mut cleaned = 0;
+            if let Ok(entries) = std::fs::read_dir(&dir) {
+                for entry in entries.flatten() {
+                    let path = entry.path();
+                    if !path.is_file() || known.contains(&path) || is_in_flight(&path, &in_flight) {
+                        continue;
+                    }
+                    if std::fs::remove_file(&path).is_ok() {
+                        cleaned += 1;
+                    }
+                }
+            }
+
+            // Loudness of files that are gone
+            if let Ok(entries) = std::fs::read_dir(dir.join(LOUDNESS_DIR)) {
+                for entry in entries.flatten() {
+                    let is_known = entry
+                        .file_name()
+                        .to_str()
+                        .is_some_and(|key| known_keys.iter().any(|known| known == key));
+                    if !is_known {
+                        let _ = std::fs::remove_file(entry.path());
+                    }
+                }
+            }
+            cleaned
+        })
+        .await;
+
+        if let Ok(count) = cleanup_result {
+            if count > 0 {
+                println!("🧹 Cache: Cleaned up {} orphaned file(s)", count);
+            }
+        }
+
+        self.evict().await;
+    }
+}
+
+/// Whether a file belongs to a download still in progress (`<key>.webm.part` etc.)
+fn is_in_flight(path: &Path, in_flight: &[String]) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| {
+            in_flight
+                .iter()
+                .any(|key| name.starts_with(&format!("{}.", key)))
+        })
+}
+
+/// Cache key for a source URL. YouTube videos are keyed by their video ID so
+/// every URL form of the same video shares one file; anything else by a
+/// stable hash of the URL.
+pub fn cache_key(url: &str) -> String {
+    let youtube_id = if let Some((_, rest)) = url.split_once("youtu.be/") {
+        rest.split(['?', '&', '/']).next()
+    } else if url.contains("youtube.com/") {
+        url.split_once("v=")
+            .and_then(|(_, rest)| rest.split('&').next())
+    } else {
+        None
+    };
+
+    match youtube_id {
+        Some(id)
+            if !id.is_empty()
+                && id
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') =>
+        {
+            format!("yt_{}", id)
+        }
+        _ => {
+            // FNV-1a, unlike DefaultHasher it is stable across Rust releases
+            let hash = url.bytes().fold(0xcbf29ce484222325u64, |hash, byte| {
+                (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3)
+            });
+            format!("url_{:016x}", hash)
+        }
+    }
+}
@@ -0,0 +1,33 @@
+use serenity::{async_trait, model::permissions::Permissions};
+
+use super::{Command, CommandContext, Registry, Reply};
+
+pub fn register(registry: &mut Registry) {
+    registry.register(Shutdown);
+}
+
+struct Shutdown;
+
+#[async_trait]
+impl Command for Shutdown {
+    fn name(&self) -> &'static str {
+        "shutdown"
+    }
+
+    fn description(&self) -> &'static str {
+        "Gracefully shut down the bot (admin only)"
+    }
+
+    fn required_permissions(&self) -> Permissions {
+        Permissions::ADMINISTRATOR
+    }
+
+    async fn run(&self, cx: CommandContext<'_>) -> Reply {
+        // Trigger graceful shutdown, main stops the client once it is done
+        let shutdown = cx.state.shutdown.clone();
+        tokio::spawn(async move {
+            shutdown.shutdown().await;
+        });
+        "🛑 Initiating graceful shutdown...".into()
+    }
+}
@@ -0,0 +1,417 @@
+use serenity::{async_trait, model::permissions::Permissions};
+
+use super::{ArgKind, ArgSpec, Command, CommandContext, Registry, Reply};
+use crate::{
+    filters::{Effect, EqBand, PITCH_LIMIT, SPEED_RANGE},
+    settings::{CROSSFADE_LIMIT, LOUDNESS_TARGET_RANGE, VOLUME_LIMIT},
+};
+
+pub fn register(registry: &mut Registry) {
+    registry.register(Volume);
+    registry.register(MaxVolume);
+    registry.register(Filter);
+    registry.register(Speed);
+    registry.register(Pitch);
+    registry.register(Normalize);
+    registry.register(Crossfade);
+}
+
+struct Volume;
+
+#[async_trait]
+impl Command for Volume {
+    fn name(&self) -> &'static str {
+        "volume"
+    }
+
+    fn description(&self) -> &'static str {
+        "Show or set the volume (0-200%)"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &["vol"]
+    }
+
+    fn args(&self) -> &'static [ArgSpec] {
+        &[ArgSpec {
+            name: "percent",
+            description: "New volume in percent",
+            kind: ArgKind::Integer,
+            required: false,
+        }]
+    }
+
+    async fn run(&self, cx: CommandContext<'_>) -> Reply {
+        let settings = cx.state.settings.get(cx.invocation.guild_id().get()).await;
+
+        let Some(requested) = cx.args.get_int("percent") else {
+            return format!(
+                "🔊 Volume: {}% (max {}%)",
+                settings.volume, settings.max_volume
+            )
+            .into();
+        };
+
+        if !(0..=VOLUME_LIMIT as i64).contains(&requested) {
+            return format!("Pick a volume between 0 and {}.", VOLUME_LIMIT).into();
+        }
+        if requested as u32 > settings.max_volume {
+            return format!(
+                "❌ The maximum volume on this server is {}%.",
+                settings.max_volume
+            )
+            .into();
+        }
+
+        let volume = cx.player().await.set_volume(requested as u32).await;
+        format!("🔊 Volume set to {}%", volume).into()
+    }
+}
+
+struct MaxVolume;
+
+#[async_trait]
+impl Command for MaxVolume {
+    fn name(&self) -> &'static str {
+        "maxvolume"
+    }
+
+    fn description(&self) -> &'static str {
+        "Set the highest volume members can choose (admin only)"
+    }
+
+    fn args(&self) -> &'static [ArgSpec] {
+        &[ArgSpec {
+            name: "percent",
+            description: "Maximum volume in percent",
+            kind: ArgKind::Integer,
+            required: true,
+        }]
+    }
+
+    fn required_permissions(&self) -> Permissions {
+        Permissions::ADMINISTRATOR
+    }
+
+    async fn run(&self, cx: CommandContext<'_>) -> Reply {
+        let max_volume = match cx.args.get_int("percent") {
+            Some(percent) if (1..=VOLUME_LIMIT as i64).contains(&percent) => percent as u32,
+            _ => return format!("Pick a maximum between 1 and {}.", VOLUME_LIMIT).into(),
+        };
+
+        let volume = cx.player().await.set_max_volume(max_volume).await;
+        format!(
+            "🔊 Maximum volume set to {}% (current volume {}%)",
+            max_volume, volume
+        )
+        .into()
+    }
+}
+
+struct Filter;
+
+#[async_trait]
+impl Command for Filter {
+    fn name(&self) -> &'static str {
+        "filter"
+    }
+
+    fn description(&self) -> &'static str {
+        "Toggle an audio effect, set the equalizer or show the active filters"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &["fx"]
+    }
+
+    fn args(&self) -> &'static [ArgSpec] {
+        &[ArgSpec {
+            name: "filter",
+            description: "Effect name, off, or eq <Hz>:<dB> ...",
+            kind: ArgKind::String,
+            required: false,
+        }]
+    }
+
+    async fn run(&self, cx: CommandContext<'_>) -> Reply {
+        let player = cx.player().await;
+        let mut filters = player.filters().await;
+
+        let Some(request) = cx.args.get("filter").map(str::trim) else {
+            let available: Vec<&str> = Effect::ALL.iter().map(|e| e.name()).collect();
+            return format!(
+                "🎛️ Active filters: {}\nEffects: {}, or `eq <Hz>:<dB> ...` for a custom equalizer",
+                filters.describe(),
+                available.join(", ")
+            )
+            .into();
+        };
+
+        let mut words = request.split_whitespace();
+        let reply = match words.next().unwrap_or("").to_lowercase().as_str() {
+            "off" | "clear" | "reset" => {
+                filters.effects.clear();
+                filters.eq.clear();
+                "🎛️ Filters cleared".to_string()
+            }
+            "eq" => {
+                let bands: Vec<&str> = words.collect();
+                if bands == ["off"] {
+                    filters.eq.clear();
+                } else {
+                    let parsed: Option<Vec<EqBand>> =
+                        bands.iter().map(|band| EqBand::parse(band)).collect();
+                    match parsed {
+                        Some(parsed) if !parsed.is_empty() => filters.eq = parsed,
+                        _ => {
+                            return "Write bands as `<Hz>:<dB>` between 20-20000 Hz and ±20 dB, e.g. `!filter eq 60:+6 4000:-3`".into()
+                        }
+                    }
+                }
+                format!("🎛️ Filters: {}", filters.describe())
+            }
+            name => match Effect::parse(name) {
+                Some(effect) => {
+                    let state = if filters.toggle(effect) { "on" } else { "off" };
+                    format!(
+                        "🎛️ {} {} • Filters: {}",
+                        effect.name(),
+                        state,
+                        filters.describe()
+                    )
+                }
+                None => {
+                    return format!("Unknown filter `{}`. Use `!filter` to list them.", name).into()
+                }
+            },
+        };
+
+        match player.set_filters(filters).await {
+            Ok(()) => reply.into(),
+            Err(note) => format!("{}\n{}", reply, note).into(),
+        }
+    }
+}
+
+struct Speed;
+
+#[async_trait]
+impl Command for Speed {
+    fn name(&self) -> &'static str {
+        "speed"
+    }
+
+    fn description(&self) -> &'static str {
+        "Show or set the playback speed (0.5-2) without changing the pitch"
+    }
+
+    fn args(&self) -> &'static [ArgSpec] {
+        &[ArgSpec {
+            name: "factor",
+            description: "Speed factor, 1 is normal",
+            kind: ArgKind::String,
+            required: false,
+        }]
+    }
+
+    async fn run(&self, cx: CommandContext<'_>) -> Reply {
+        let player = cx.player().await;
+        let mut filters = player.filters().await;
+
+        let Some(requested) = cx.args.get("factor") else {
+            return format!("⏱️ Speed: {}×", filters.speed).into();
+        };
+        let speed = match requested.trim_end_matches(['x', '×']).parse::<f64>() {
+            Ok(speed) if SPEED_RANGE.contains(&speed) => speed,
+            _ => {
+                return format!(
+                    "Pick a speed between {} and {}.",
+                    SPEED_RANGE.start(),
+                    SPEED_RANGE.end()
+                )
+                .into()
+            }
+        };
+
+        filters.speed = speed;
+        let reply = format!("⏱️ Speed set to {}×", speed);
+        match player.set_filters(filters).await {
+            Ok(()) => reply.into(),
+            Err(note) => format!("{}\n{}", reply, note).into(),
+        }
+    }
+}
+
+struct Pitch;
+
+#[async_trait]
+impl Command for Pitch {
+    fn name(&self) -> &'static str {
+        "pitch"
+    }
+
+    fn description(&self) -> &'static str {
+        "Show or shift the pitch in semitones without changing the speed"
+    }
+
+    fn args(&self) -> &'static [ArgSpec] {
+        &[ArgSpec {
+            name: "semitones",
+            description: "Semitones up or down, 0 is normal",
+            kind: ArgKind::Integer,
+            required: false,
+        }]
+    }
+
+    async fn run(&self, cx: CommandContext<'_>) -> Reply {
+        let player = cx.player().await;
+        let mut filters = player.filters().await;
+
+        let Some(requested) = cx.args.get("semitones") else {
+            return format!("🎚️ Pitch: {:+} semitone(s)", filters.pitch).into();
+        };
+        let pitch = match requested.parse::<i32>() {
+            Ok(pitch) if (-PITCH_LIMIT..=PITCH_LIMIT).contains(&pitch) => pitch,
+            _ => {
+                return format!(
+                    "Pick a shift between -{} and +{} semitones.",
+                    PITCH_LIMIT, PITCH_LIMIT
+                )
+                .into()
+            }
+        };
+
+        filters.pitch = pitch;
+        let reply = format!("🎚️ Pitch set to {:+} semitone(s)", pitch);
+        match player.set_filters(filters).await {
+            Ok(()) => reply.into(),
+            Err(note) => format!("{}\n{}", reply, note).into(),
+        }
+    }
+}
+
+struct Normalize;
+
+#[async_trait]
+impl Command for Normalize {
+    fn name(&self) -> &'static str {
+        "normalize"
+    }
+
+    fn description(&self) -> &'static str {
+        "Show or set loudness normalization (on, off or a target in LUFS)"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &["normalise"]
+    }
+
+    fn args(&self) -> &'static [ArgSpec] {
+        &[ArgSpec {
+            name: "mode",
+            description: "on, off, or a target loudness like -14",
+            kind: ArgKind::String,
+            required: false,
+        }]
+    }
+
+    async fn run(&self, cx: CommandContext<'_>) -> Reply {
+        let settings = cx.state.settings.get(cx.invocation.guild_id().get()).await;
+        let describe = |normalize: bool, target: i32| {
+            if normalize {
+                format!("on, target {} LUFS", target)
+            } else {
+                "off".to_string()
+            }
+        };
+
+        let Some(mode) = cx.args.get("mode") else {
+            return format!(
+                "📏 Loudness normalization: {}",
+                describe(settings.normalize, settings.target_lufs)
+            )
+            .into();
+        };
+
+        let (normalize, target) = match mode.to_lowercase().as_str() {
+            "on" => (true, settings.target_lufs),
+            "off" => (false, settings.target_lufs),
+            value => match value.trim_end_matches("lufs").trim().parse::<i32>() {
+                Ok(target) if LOUDNESS_TARGET_RANGE.contains(&target) => (true, target),
+                _ => {
+                    return format!(
+                        "Use `on`, `off` or a target between {} and {} LUFS.",
+                        LOUDNESS_TARGET_RANGE.start(),
+                        LOUDNESS_TARGET_RANGE.end()
+                    )
+                    .into()
+                }
+            },
+        };
+
+        let settings = cx.player().await.set_normalization(normalize, target).await;
+        format!(
+            "📏 Loudness normalization {}",
+            describe(settings.normalize, settings.target_lufs)
+        )
+        .into()
+    }
+}
+
+struct Crossfade;
+
+#[async_trait]
+impl Command for Crossfade {
+    fn name(&self) -> &'static str {
+        "crossfade"
+    }
+
+    fn description(&self) -> &'static str {
+        "Show or set the crossfade between songs in seconds (0-12)"
+    }
+
+    fn args(&self) -> &'static [ArgSpec] {
+        &[ArgSpec {
+            name: "seconds",
+            description: "Overlap between songs, 0 turns it off",
+            kind: ArgKind::Integer,
+            required: false,
+        }]
+    }
+
+    async fn run(&self, cx: CommandContext<'_>) -> Reply {
+        let guild_id = cx.invocation.guild_id().get();
+
+        let Some(requested) = cx.args.get_int("seconds") else {
+            let seconds = cx.state.settings.get(guild_id).await.crossfade_secs;
+            return match seconds {
+                0 => "🔀 Crossfade: off".into(),
+                seconds => format!("🔀 Crossfade: {}s", seconds).into(),
+            };
+        };
+
+        if !(0..=CROSSFADE_LIMIT as i64).contains(&requested) {
+            return format!(
+                "Pick a crossfade between 0 and {} seconds.",
+                CROSSFADE_LIMIT
+            )
+            .into();
+        }
+
+        // Picked up when the next song starts
+        cx.state
+            .settings
+            .update(guild_id, |settings| {
+                settings.crossfade_secs = requested as u32
+            })
+            .await;
+        match requested {
+            0 => "🔀 Crossfade turned off".into(),
+            seconds => format!(
+                "🔀 Crossfade set to {}s, starting with the next song",
+                seconds
+            )
+            .into(),
+        }
+    }
+}
@@ -0,0 +1,451 @@
+mod admin;
+mod audio;
+mod playback;
+mod queue;
+mod search;
+mod voice;
+
+pub use queue::PAGE_BUTTON_PREFIX;
+pub use search::{SEARCH_MENU_PREFIX, SEARCH_TIMEOUT};
+
+use serenity::{
+    async_trait,
+    builder::{
+        CreateActionRow, CreateCommand, CreateCommandOption, CreateEmbed,
+        CreateInteractionResponseMessage, CreateMessage, EditInteractionResponse,
+    },
+    model::{
+        application::{CommandDataOption, CommandDataOptionValue, CommandOptionType},
+        id::{ChannelId, GuildId, UserId},
+        permissions::Permissions,
+    },
+    prelude::*,
+};
+use songbird::{Call, Songbird};
+use std::{collections::HashMap, sync::Arc};
+
+use crate::player::Player;
+use crate::state::BotState;
+
+/// Who invoked a command and where, independent of the entry point
+/// (`!` prefix message or slash command)
+pub struct Invocation {
+    pub guild_id: Option<GuildId>,
+    /// Text channel the command was sent from
+    pub channel_id: ChannelId,
+    pub user_id: UserId,
+    pub permissions: Option<Permissions>,
+}
+
+impl Invocation {
+    /// Guild the command was run in. Only call this from guild-only commands,
+    /// the registry refuses to dispatch those outside a guild.
+    pub fn guild_id(&self) -> GuildId {
+        self.guild_id
+            .expect("guild-only command dispatched outside a guild")
+    }
+}
+
+/// Reply to a command: text, optionally with an embed and message components
+#[derive(Default)]
+pub struct Reply {
+    pub content: Option<String>,
+    pub embed: Option<CreateEmbed>,
+    pub components: Vec<CreateActionRow>,
+}
+
+impl Reply {
+    pub fn embed(embed: CreateEmbed) -> Self {
+        Self {
+            embed: Some(embed),
+            ..Default::default()
+        }
+    }
+
+    pub fn components(mut self, components: Vec<CreateActionRow>) -> Self {
+        self.components = components;
+        self
+    }
+
+    /// Build a message for the `!` prefix entry point
+    pub fn into_message(self) -> CreateMessage {
+        let mut message = CreateMessage::new().components(self.components);
+        if let Some(content) = self.content {
+            message = message.content(content);
+        }
+        if let Some(embed) = self.embed {
+            message = message.embed(embed);
+        }
+        message
+    }
+
+    /// Build the edit of a deferred slash command response
+    pub fn into_edit_response(self) -> EditInteractionResponse {
+        let mut response = EditInteractionResponse::new().components(self.components);
+        if let Some(content) = self.content {
+            response = response.content(content);
+        }
+        if let Some(embed) = self.embed {
+            response = response.embed(embed);
+        }
+        response
+    }
+
+    /// Build the response to a message component interaction
+    pub fn into_response_message(self) -> CreateInteractionResponseMessage {
+        let mut response = CreateInteractionResponseMessage::new().components(self.components);
+        if let Some(content) = self.content {
+            response = response.content(content);
+        }
+        if let Some(embed) = self.embed {
+            response = response.embed(embed);
+        }
+        response
+    }
+}
+
+impl From<String> for Reply {
+    fn from(content: String) -> Self {
+        Self {
+            content: Some(content),
+            ..Default::default()
+        }
+    }
+}
+
+impl From<&str> for Reply {
+    fn from(content: &str) -> Self {
+        content.to_string().into()
+    }
+}
+
+/// Type of a declared command argument
+#[derive(Clone, Copy, PartialEq)]
+pub enum ArgKind {
+    String,
+    Integer,
+}
+
+/// Declared argument of a command, used for parsing, usage text and slash registration
+pub struct ArgSpec {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub kind: ArgKind,
+    pub required: bool,
+}
+
+/// Raw arguments as received from one of the entry points
+pub enum RawArgs<'a> {
+    /// Text after the command name of a `!` prefix message
+    Text(Option<&'a str>),
+    /// Typed options of a slash command
+    Options(&'a [CommandDataOption]),
+}
+
+/// Arguments parsed and validated against a command's `ArgSpec`s
+#[derive(Default)]
+pub struct Args {
+    values: HashMap<&'static str, String>,
+}
+
+impl Args {
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.values.get(name).map(String::as_str)
+    }
+
+    pub fn get_int(&self, name: &str) -> Option<i64> {
+        self.get(name).and_then(|value| value.parse().ok())
+    }
+}
+
+/// Everything a command needs to run
+pub struct CommandContext<'a> {
+    pub ctx: &'a Context,
+    pub state: &'a BotState,
+    pub invocation: &'a Invocation,
+    pub args: Args,
+}
+
+impl CommandContext<'_> {
+    /// Playback controller of the invoking guild
+    pub async fn player(&self) -> Player {
+        self.state
+            .get_or_create_player(
+                voice_manager(self.ctx).await,
+                self.ctx.http.clone(),
+                self.invocation.guild_id(),
+            )
+            .await
+    }
+}
+
+#[async_trait]
+pub trait Command: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    fn description(&self) -> &'static str;
+
+    /// Alternative names accepted by the `!` prefix entry point
+    fn aliases(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    /// Arguments in positional order. For `!` prefix commands the last
+    /// argument receives the rest of the message.
+    fn args(&self) -> &'static [ArgSpec] {
+        &[]
+    }
+
+    /// Permissions the invoking member must hold
+    fn required_permissions(&self) -> Permissions {
+        Permissions::empty()
+    }
+
+    /// Whether the command can only run inside a guild
+    fn guild_only(&self) -> bool {
+        true
+    }
+
+    /// Run the command and return the reply
+    async fn run(&self, cx: CommandContext<'_>) -> Reply;
+}
+
+/// All commands the bot understands, looked up by name or alias
+pub struct Registry {
+    commands: Vec<Box<dyn Command>>,
+}
+
+impl Registry {
+    pub fn new() -> Self {
+        let mut registry = Self {
+            commands: Vec::new(),
+        };
+        voice::register(&mut registry);
+        playback::register(&mut registry);
+        queue::register(&mut registry);
+        search::register(&mut registry);
+        audio::register(&mut registry);
+        admin::register(&mut registry);
+        registry
+    }
+
+    pub fn register(&mut self, command: impl Command + 'static) {
+        self.commands.push(Box::new(command));
+    }
+
+    /// Find a command by name or alias
+    pub fn find(&self, name: &str) -> Option<&dyn Command> {
+        self.commands
+            .iter()
+            .find(|c| c.name() == name || c.aliases().contains(&name))
+            .map(|c| c.as_ref())
+    }
+
+    /// Closest known command name, if any is near enough to be a likely typo
+    fn suggest(&self, name: &str) -> Option<&'static str> {
+        self.commands
+            .iter()
+            .flat_map(|c| std::iter::once(c.name()).chain(c.aliases().iter().copied()))
+            .map(|candidate| (candidate, edit_distance(name, candidate)))
+            .filter(|(candidate, distance)| *distance <= (candidate.len() / 3).max(1))
+            .min_by_key(|(_, distance)| *distance)
+            .map(|(candidate, _)| candidate)
+    }
+
+    /// Slash command definitions registered with Discord on startup
+    pub fn slash_commands(&self) -> Vec<CreateCommand> {
+        self.commands
+            .iter()
+            .map(|command| {
+                let mut definition = CreateCommand::new(command.name())
+                    .description(command.description())
+                    .dm_permission(!command.guild_only());
+
+                if !command.required_permissions().is_empty() {
+                    definition = definition.default_member_permissions(command.required_permissions());
+                }
+
+                for arg in command.args() {
+                    let kind = match arg.kind {
+                        ArgKind::String => CommandOptionType::String,
+                        ArgKind::Integer => CommandOptionType::Integer,
+                    };
+                    definition = definition.add_option(
+                        CreateCommandOption::new(kind, arg.name, arg.description)
+                            .required(arg.required),
+                    );
+                }
+                definition
+            })
+            .collect()
+    }
+
+    /// Route an invocation to its command and return the reply.
+    /// Returns `None` when there is nothing to reply.
+    pub async fn dispatch(
+        &self,
+        ctx: &Context,
+        state: &BotState,
+        invocation: &Invocation,
+        name: &str,
+        raw: RawArgs<'_>,
+    ) -> Option<Reply> {
+        let command = match self.find(name) {
+            Some(command) => command,
+            None => {
+                // Ignore things like "!!!" that were never meant as commands
+                if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric()) {
+                    return None;
+                }
+                // Only answer near misses, other bots may share the prefix
+                let suggestion = self.suggest(name)?;
+                return Some(
+                    format!(
+                        "Unknown command `!{}`. Did you mean `!{}`?",
+                        name, suggestion
+                    )
+                    .into(),
+                );
+            }
+        };
+
+        if command.guild_only() && invocation.guild_id.is_none() {
+            return Some("This command can only be used in a server.".into());
+        }
+
+        let required = command.required_permissions();
+        if !required.is_empty() {
+            let granted = invocation.permissions.unwrap_or_else(Permissions::empty);
+            if !granted.administrator() && !granted.contains(required) {
+                return Some(
+                    format!(
+                        "❌ You need the {} permission(s) to use `!{}`.",
+                        required,
+                        command.name()
+                    )
+                    .into(),
+                );
+            }
+        }
+
+        let args = match parse_args(command, raw) {
+            Ok(args) => args,
+            Err(e) => return Some(e.into()),
+        };
+
+        Some(
+            command
+                .run(CommandContext {
+                    ctx,
+                    state,
+                    invocation,
+                    args,
+                })
+                .await,
+        )
+    }
+}
+
+/// `Usage: !name <required> [optional]` line built from the argument schema
+pub fn usage(command: &dyn Command) -> String {
+    let mut usage = format!("Usage: !{}", command.name());
+    for arg in command.args() {
+        if arg.required {
+            usage.push_str(&format!(" <{}>", arg.name));
+        } else {
+            usage.push_str(&format!(" [{}]", arg.name));
+        }
+    }
+    usage
+}
+
+fn parse_args(command: &dyn Command, raw: RawArgs<'_>) -> Result<Args, String> {
+    let specs = command.args();
+    let mut args = Args::default();
+
+    match raw {
+        RawArgs::Text(text) => {
+            let mut rest = text.unwrap_or("").trim();
+            for (i, spec) in specs.iter().enumerate() {
+                if rest.is_empty() {
+                    break;
+                }
+                let value = if i == specs.len() - 1 {
+                    std::mem::take(&mut rest)
+                } else {
+                    let (value, remainder) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+                    rest = remainder.trim_start();
+                    value
+                };
+                args.values.insert(spec.name, value.to_string());
+            }
+        }
+        RawArgs::Options(options) => {
+            for option in options {
+                let Some(spec) = specs.iter().find(|s| s.name == option.name) else {
+                    continue;
+                };
+                let value = match &option.value {
+                    CommandDataOptionValue::String(s) => s.clone(),
+                    CommandDataOptionValue::Integer(i) => i.to_string(),
+                    CommandDataOptionValue::Number(n) => n.to_string(),
+                    CommandDataOptionValue::Boolean(b) => b.to_string(),
+                    _ => continue,
+                };
+                args.values.insert(spec.name, value);
+            }
+        }
+    }
+
+    for spec in specs {
+        match args.get(spec.name) {
+            None if spec.required => return Err(usage(command)),
+            Some(value) if spec.kind == ArgKind::Integer && value.parse::<i64>().is_err() => {
+                return Err(format!("`{}` must be a whole number. {}", spec.name, usage(command)));
+            }
+            _ => {}
+        }
+    }
+
+    Ok(args)
+}
+
+/// Levenshtein distance between two command names
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+/// Songbird voice manager registered on the client
+pub async fn voice_manager(ctx: &Context) -> Arc<Songbird> {
+    songbird::get(ctx)
+        .await
+        .expect("Songbird voice client registered at startup")
+}
+
+/// Call for the guild, if the bot is connected to voice there
+pub async fn current_call(ctx: &Context, guild_id: GuildId) -> Option<Arc<Mutex<Call>>> {
+    voice_manager(ctx).await.get(guild_id)
+}
+
+/// Voice channel the invoking user is currently in
+pub fn user_voice_channel(ctx: &Context, invocation: &Invocation) -> Option<ChannelId> {
+    let guild_id = invocation.guild_id?;
+    ctx.cache.guild(guild_id).and_then(|guild| {
+        guild
+            .voice_states
+            .get(&invocation.user_id)
+            .and_then(|v| v.channel_id)
+    })
+}
@@ -0,0 +1,453 @@
+use serenity::async_trait;
+
+use super::{
+    user_voice_channel, voice_manager, ArgKind, ArgSpec, Command, CommandContext, Registry, Reply,
+};
+use crate::music::expand_playlist;
+use crate::queue::{format_duration, parse_duration, shuffle, LoopMode, Track};
+use crate::sources::{Capabilities, Playlist};
+
+pub fn register(registry: &mut Registry) {
+    registry.register(Play);
+    registry.register(PlayNext);
+    registry.register(Pause);
+    registry.register(Resume);
+    registry.register(Skip);
+    registry.register(Stop);
+    registry.register(Loop);
+    registry.register(Seek);
+    registry.register(Forward);
+    registry.register(Rewind);
+}
+
+struct Play;
+
+#[async_trait]
+impl Command for Play {
+    fn name(&self) -> &'static str {
+        "play"
+    }
+
+    fn description(&self) -> &'static str {
+        "Play a song or playlist by name or URL, --shuffle shuffles playlists"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &["p"]
+    }
+
+    fn args(&self) -> &'static [ArgSpec] {
+        &[ArgSpec {
+            name: "query",
+            description: "Song, URL or playlist, sc:/bc:/yt:/local: picks the source",
+            kind: ArgKind::String,
+            required: true,
+        }]
+    }
+
+    async fn run(&self, cx: CommandContext<'_>) -> Reply {
+        enqueue(&cx, false).await
+    }
+}
+
+struct PlayNext;
+
+#[async_trait]
+impl Command for PlayNext {
+    fn name(&self) -> &'static str {
+        "playnext"
+    }
+
+    fn description(&self) -> &'static str {
+        "Queue a song right after the current one"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &["pn"]
+    }
+
+    fn args(&self) -> &'static [ArgSpec] {
+        Play.args()
+    }
+
+    async fn run(&self, cx: CommandContext<'_>) -> Reply {
+        enqueue(&cx, true).await
+    }
+}
+
+/// Shared implementation of `!play` and `!playnext`: queue the query at the
+/// back (or right after the current track when `next` is set) and start
+/// playing if nothing was queued. Playlist links are expanded into one entry
+/// per track, shuffled first when the query starts with `--shuffle`.
+async fn enqueue(cx: &CommandContext<'_>, next: bool) -> Reply {
+    let (shuffled, query) = split_shuffle_flag(cx.args.get("query").unwrap_or_default());
+    let guild_id = cx.invocation.guild_id();
+    let queue = cx.state.get_or_create_queue(guild_id.get()).await;
+
+    // Ensure bot is in voice channel
+    let connect_to = match user_voice_channel(cx.ctx, cx.invocation) {
+        Some(channel) => channel,
+        None => return "You need to be in a voice channel!".into(),
+    };
+
+    let playlist = match expand_playlist(query, cx.state.playlist_limit).await {
+        Ok(playlist) => playlist,
+        Err(e) => return format!("❌ Couldn't load that playlist: {}", e).into(),
+    };
+
+    let manager = voice_manager(cx.ctx).await;
+    if manager.get(guild_id).is_none() {
+        let _ = manager.join(guild_id, connect_to).await;
+        cx.player()
+            .await
+            .set_text_channel(cx.invocation.channel_id)
+            .await;
+    }
+
+    let (user_id, channel_id) = (cx.invocation.user_id, cx.invocation.channel_id);
+    let mut tracks: Vec<Track> = match &playlist {
+        Some(playlist) => playlist
+            .entries
+            .iter()
+            .map(|entry| {
+                let mut track = Track::new(entry.query.clone(), user_id, channel_id);
+                track.apply_metadata(&entry.metadata, Capabilities::default());
+                track
+            })
+            .collect(),
+        None => vec![Track::new(query.to_string(), user_id, channel_id)],
+    };
+    if shuffled {
+        shuffle(&mut tracks);
+    }
+
+    // Add to queue
+    let mut queue_lock = queue.lock().await;
+    let position = if next && !queue_lock.is_empty() {
+        // Index 0 is the playing track, never insert in front of it
+        queue_lock.splice(1..1, tracks.iter().cloned());
+        1
+    } else {
+        queue_lock.extend(tracks.iter().cloned());
+        queue_lock.len() - tracks.len()
+    };
+    drop(queue_lock);
+
+    if position > 0 {
+        // Validate and fill in metadata now rather than when it reaches the
+        // head. Playlist entries already have theirs from the listing.
+        if playlist.is_none() {
+            cx.state
+                .resolver
+                .resolve(cx.ctx.http.clone(), queue.clone(), tracks[0].clone());
+        }
+        cx.state.prefetcher.prefetch(guild_id.get(), &queue).await;
+    }
+
+    let added = match &playlist {
+        Some(playlist) => playlist_added(playlist, tracks.len(), shuffled),
+        None => format!("Added to queue (position {})", position),
+    };
+
+    // Play immediately if nothing is playing. Failures are reported to the
+    // channel by the player after it has retried.
+    let player = cx.player().await;
+    if player.is_active().await {
+        return added.into();
+    }
+    match player.play_next().await {
+        // The player posts the now-playing embed itself
+        Some(started) if playlist.is_some() => {
+            format!("{}\n🎵 Starting **{}**", added, started.display_title())
+        }
+        Some(started) => format!("🎵 Starting **{}**", started.display_title()),
+        None if queue
+            .lock()
+            .await
+            .iter()
+            .any(|queued| tracks.iter().any(|t| t.id == queued.id)) =>
+        {
+            added
+        }
+        None => "❌ Couldn't play that song.".to_string(),
+    }
+    .into()
+}
+
+/// Split a leading `--shuffle` off a play query
+fn split_shuffle_flag(query: &str) -> (bool, &str) {
+    let query = query.trim();
+    match query.strip_prefix("--shuffle") {
+        Some(rest) if rest.is_empty() || rest.starts_with(char::is_whitespace) => {
+            (true, rest.trim_start())
+        }
+        _ => (false, query),
+    }
+}
+
+/// Confirmation for a queued playlist
+fn playlist_added(playlist: &Playlist, added: usize, shuffled: bool) -> String {
+    let mut text = format!(
+        "📃 Added {} track(s) from **{}**",
+        added,
+        playlist.title.as_deref().unwrap_or("the playlist")
+    );
+    if let Some(total) = playlist.total.filter(|total| *total > added) {
+        text.push_str(&format!(" (the first {} of {})", added, total));
+    }
+    if shuffled {
+        text.push_str(", shuffled");
+    }
+    text
+}
+
+struct Pause;
+
+#[async_trait]
+impl Command for Pause {
+    fn name(&self) -> &'static str {
+        "pause"
+    }
+
+    fn description(&self) -> &'static str {
+        "Pause the current song"
+    }
+
+    async fn run(&self, cx: CommandContext<'_>) -> Reply {
+        match cx.player().await.pause().await {
+            Ok(()) => "Paused ⏸️".to_string(),
+            Err(e) => e,
+        }
+        .into()
+    }
+}
+
+struct Resume;
+
+#[async_trait]
+impl Command for Resume {
+    fn name(&self) -> &'static str {
+        "resume"
+    }
+
+    fn description(&self) -> &'static str {
+        "Resume playback"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &["unpause"]
+    }
+
+    async fn run(&self, cx: CommandContext<'_>) -> Reply {
+        match cx.player().await.resume().await {
+            Ok(()) => "Resumed ▶️".to_string(),
+            Err(e) => e,
+        }
+        .into()
+    }
+}
+
+struct Skip;
+
+#[async_trait]
+impl Command for Skip {
+    fn name(&self) -> &'static str {
+        "skip"
+    }
+
+    fn description(&self) -> &'static str {
+        "Skip to the next song"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &["next", "s"]
+    }
+
+    async fn run(&self, cx: CommandContext<'_>) -> Reply {
+        match cx.player().await.skip().await {
+            Ok(()) => "Skipped ⏭️".to_string(),
+            Err(e) => e,
+        }
+        .into()
+    }
+}
+
+struct Stop;
+
+#[async_trait]
+impl Command for Stop {
+    fn name(&self) -> &'static str {
+        "stop"
+    }
+
+    fn description(&self) -> &'static str {
+        "Stop and clear the queue"
+    }
+
+    async fn run(&self, cx: CommandContext<'_>) -> Reply {
+        let player = cx.player().await;
+        player.stop().await;
+        player.check_idle().await;
+        "Stopped and cleared queue ⏹️".into()
+    }
+}
+
+struct Loop;
+
+#[async_trait]
+impl Command for Loop {
+    fn name(&self) -> &'static str {
+        "loop"
+    }
+
+    fn description(&self) -> &'static str {
+        "Set the loop mode (off, track or queue), or cycle through them"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &["repeat"]
+    }
+
+    fn args(&self) -> &'static [ArgSpec] {
+        &[ArgSpec {
+            name: "mode",
+            description: "off, track or queue",
+            kind: ArgKind::String,
+            required: false,
+        }]
+    }
+
+    async fn run(&self, cx: CommandContext<'_>) -> Reply {
+        let guild_id = cx.invocation.guild_id().get();
+        let mut loop_modes = cx.state.loop_modes.lock().await;
+        let current = loop_modes.get(&guild_id).copied().unwrap_or_default();
+
+        let mode = match cx.args.get("mode") {
+            Some(value) => match LoopMode::parse(value) {
+                Some(mode) => mode,
+                None => {
+                    return format!("Unknown loop mode `{}`. Use off, track or queue.", value)
+                        .into()
+                }
+            },
+            None => current.next(),
+        };
+
+        loop_modes.insert(guild_id, mode);
+        drop(loop_modes);
+
+        cx.player().await.refresh_panel().await;
+        format!("Loop mode: {}", mode.label()).into()
+    }
+}
+
+struct Seek;
+
+#[async_trait]
+impl Command for Seek {
+    fn name(&self) -> &'static str {
+        "seek"
+    }
+
+    fn description(&self) -> &'static str {
+        "Jump to a position in the current song"
+    }
+
+    fn args(&self) -> &'static [ArgSpec] {
+        &[ArgSpec {
+            name: "position",
+            description: "Position like 1:23 or 83",
+            kind: ArgKind::String,
+            required: true,
+        }]
+    }
+
+    async fn run(&self, cx: CommandContext<'_>) -> Reply {
+        let value = cx.args.get("position").unwrap_or_default();
+        let Some(position) = parse_duration(value) else {
+            return format!("`{}` isn't a position. Use something like 1:23.", value).into();
+        };
+
+        seek_reply(cx.player().await.seek(position).await)
+    }
+}
+
+/// Seconds to move when `!forward`/`!rewind` get no argument
+const DEFAULT_SEEK_STEP: i64 = 10;
+
+struct Forward;
+
+#[async_trait]
+impl Command for Forward {
+    fn name(&self) -> &'static str {
+        "forward"
+    }
+
+    fn description(&self) -> &'static str {
+        "Fast-forward the current song"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &["ff"]
+    }
+
+    fn args(&self) -> &'static [ArgSpec] {
+        &[ArgSpec {
+            name: "seconds",
+            description: "Seconds to skip ahead (default 10)",
+            kind: ArgKind::Integer,
+            required: false,
+        }]
+    }
+
+    async fn run(&self, cx: CommandContext<'_>) -> Reply {
+        let seconds = cx.args.get_int("seconds").unwrap_or(DEFAULT_SEEK_STEP);
+        if seconds < 0 {
+            return "Give a positive number of seconds, `!rewind` goes back.".into();
+        }
+        seek_reply(cx.player().await.seek_by(seconds).await)
+    }
+}
+
+struct Rewind;
+
+#[async_trait]
+impl Command for Rewind {
+    fn name(&self) -> &'static str {
+        "rewind"
+    }
+
+    fn description(&self) -> &'static str {
+        "Rewind the current song"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &["rw"]
+    }
+
+    fn args(&self) -> &'static [ArgSpec] {
+        &[ArgSpec {
+            name: "seconds",
+            description: "Seconds to go back (default 10)",
+            kind: ArgKind::Integer,
+            required: false,
+        }]
+    }
+
+    async fn run(&self, cx: CommandContext<'_>) -> Reply {
+        let seconds = cx.args.get_int("seconds").unwrap_or(DEFAULT_SEEK_STEP);
+        if seconds < 0 {
+            return "Give a positive number of seconds, `!forward` goes ahead.".into();
+        }
+        seek_reply(cx.player().await.seek_by(-seconds).await)
+    }
+}
+
+/// Reply of the seek commands, reporting the new position
+fn seek_reply(result: Result<std::time::Duration, String>) -> Reply {
+    match result {
+        Ok(position) => format!("⏩ Now at {}", format_duration(position)),
+        Err(e) => e,
+    }
+    .into()
+}
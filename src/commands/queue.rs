@@ -0,0 +1,361 @@
+use serenity::{
+    async_trait,
+    builder::{CreateActionRow, CreateButton, CreateEmbed, CreateEmbedFooter},
+    model::application::ButtonStyle,
+};
+use std::time::Duration;
+
+use super::{ArgKind, ArgSpec, Command, CommandContext, Registry, Reply};
+use crate::queue::{format_duration, shuffle, total_duration, LoopMode};
+
+pub fn register(registry: &mut Registry) {
+    registry.register(ShowQueue);
+    registry.register(Remove);
+    registry.register(Move);
+    registry.register(Shuffle);
+    registry.register(Clear);
+    registry.register(SkipTo);
+}
+
+/// Entries per `!queue` page
+const PAGE_SIZE: usize = 10;
+
+/// Prefix of the custom ids of the `!queue` page buttons, followed by the page
+pub const PAGE_BUTTON_PREFIX: &str = "queue:";
+
+struct ShowQueue;
+
+#[async_trait]
+impl Command for ShowQueue {
+    fn name(&self) -> &'static str {
+        "queue"
+    }
+
+    fn description(&self) -> &'static str {
+        "Show the current queue"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &["q"]
+    }
+
+    fn args(&self) -> &'static [ArgSpec] {
+        &[ArgSpec {
+            name: "page",
+            description: "Page to show",
+            kind: ArgKind::Integer,
+            required: false,
+        }]
+    }
+
+    async fn run(&self, cx: CommandContext<'_>) -> Reply {
+        let queue = cx
+            .state
+            .get_or_create_queue(cx.invocation.guild_id().get())
+            .await;
+        let tracks = queue.lock().await.clone();
+
+        if tracks.is_empty() {
+            return "Queue is empty!".into();
+        }
+
+        // Times are shown as they play at the current speed
+        let player = cx.player().await;
+        let elapsed = player.position().await.unwrap_or_default();
+        let speed = player.filters().await.effective_speed();
+        let pages = tracks.len().div_ceil(PAGE_SIZE);
+        let page = cx.args.get_int("page").unwrap_or(1).clamp(1, pages as i64) as usize;
+
+        // Time until each entry starts, unknown once an earlier one has no duration
+        let mut starts_in = Some(Duration::ZERO);
+        let mut lines = Vec::new();
+        for (i, track) in tracks.iter().enumerate() {
+            if (page - 1) * PAGE_SIZE <= i && i < page * PAGE_SIZE {
+                lines.push(if i == 0 {
+                    format!("▶️ {}", track.summary(speed))
+                } else {
+                    let eta = starts_in
+                        .map(format_duration)
+                        .unwrap_or_else(|| "?".to_string());
+                    format!("`{}.` {} • plays in {}", i, track.summary(speed), eta)
+                });
+            }
+
+            let remaining = if i == 0 {
+                track.duration.map(|d| d.saturating_sub(elapsed))
+            } else {
+                track.duration
+            };
+            let remaining = remaining.map(|d| d.div_f64(speed));
+            starts_in = starts_in.zip(remaining).map(|(start, d)| start + d);
+        }
+
+        let (total, unknown) = total_duration(tracks.iter());
+        let mut footer = format!(
+            "Page {}/{} • {} song(s) • Total remaining: {}",
+            page,
+            pages,
+            tracks.len(),
+            format_duration(total.saturating_sub(elapsed).div_f64(speed))
+        );
+        if unknown > 0 {
+            footer.push_str(&format!(" (+{} track(s) of unknown length)", unknown));
+        }
+
+        let embed = CreateEmbed::new()
+            .title("Queue")
+            .description(lines.join("\n"))
+            .footer(CreateEmbedFooter::new(footer));
+
+        let mut reply = Reply::embed(embed);
+        if pages > 1 {
+            let button = |target: usize, label: &str, disabled: bool| {
+                CreateButton::new(format!("{}{}", PAGE_BUTTON_PREFIX, target))
+                    .label(label)
+                    .style(ButtonStyle::Secondary)
+                    .disabled(disabled)
+            };
+            reply = reply.components(vec![CreateActionRow::Buttons(vec![
+                button(page - 1, "◀️ Previous", page == 1),
+                button(page + 1, "Next ▶️", page == pages),
+            ])]);
+        }
+        reply
+    }
+}
+
+/// Validate a user-facing queue position. Position 0 is the playing track and
+/// can't be targeted by queue edits.
+fn upcoming_index(position: Option<i64>, len: usize) -> Result<usize, String> {
+    match position {
+        Some(position) if position >= 1 && (position as usize) < len => Ok(position as usize),
+        _ if len <= 1 => Err("There is nothing queued after the current song.".to_string()),
+        _ => Err(format!("Pick a position between 1 and {}.", len - 1)),
+    }
+}
+
+struct Remove;
+
+#[async_trait]
+impl Command for Remove {
+    fn name(&self) -> &'static str {
+        "remove"
+    }
+
+    fn description(&self) -> &'static str {
+        "Remove a song from the queue"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &["rm"]
+    }
+
+    fn args(&self) -> &'static [ArgSpec] {
+        &[ArgSpec {
+            name: "position",
+            description: "Queue position as shown by !queue",
+            kind: ArgKind::Integer,
+            required: true,
+        }]
+    }
+
+    async fn run(&self, cx: CommandContext<'_>) -> Reply {
+        let guild_id = cx.invocation.guild_id().get();
+        let queue = cx.state.get_or_create_queue(guild_id).await;
+
+        let removed = {
+            let mut queue_lock = queue.lock().await;
+            match upcoming_index(cx.args.get_int("position"), queue_lock.len()) {
+                Ok(index) => queue_lock.remove(index),
+                Err(e) => return e.into(),
+            }
+        };
+
+        // Release the download if it was prefetched
+        cx.state.prefetcher.prefetch(guild_id, &queue).await;
+        format!("🗑️ Removed **{}**", removed.display_title()).into()
+    }
+}
+
+struct Move;
+
+#[async_trait]
+impl Command for Move {
+    fn name(&self) -> &'static str {
+        "move"
+    }
+
+    fn description(&self) -> &'static str {
+        "Move a song to another position in the queue"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &["mv"]
+    }
+
+    fn args(&self) -> &'static [ArgSpec] {
+        &[
+            ArgSpec {
+                name: "from",
+                description: "Current queue position",
+                kind: ArgKind::Integer,
+                required: true,
+            },
+            ArgSpec {
+                name: "to",
+                description: "New queue position",
+                kind: ArgKind::Integer,
+                required: true,
+            },
+        ]
+    }
+
+    async fn run(&self, cx: CommandContext<'_>) -> Reply {
+        let guild_id = cx.invocation.guild_id().get();
+        let queue = cx.state.get_or_create_queue(guild_id).await;
+
+        let (title, to) = {
+            let mut queue_lock = queue.lock().await;
+            let len = queue_lock.len();
+            let from = match upcoming_index(cx.args.get_int("from"), len) {
+                Ok(index) => index,
+                Err(e) => return e.into(),
+            };
+            let to = match upcoming_index(cx.args.get_int("to"), len) {
+                Ok(index) => index,
+                Err(e) => return e.into(),
+            };
+
+            let track = queue_lock.remove(from);
+            let title = track.display_title().to_string();
+            queue_lock.insert(to, track);
+            (title, to)
+        };
+
+        cx.state.prefetcher.prefetch(guild_id, &queue).await;
+        format!("↕️ Moved **{}** to position {}", title, to).into()
+    }
+}
+
+struct Shuffle;
+
+#[async_trait]
+impl Command for Shuffle {
+    fn name(&self) -> &'static str {
+        "shuffle"
+    }
+
+    fn description(&self) -> &'static str {
+        "Shuffle the upcoming songs"
+    }
+
+    async fn run(&self, cx: CommandContext<'_>) -> Reply {
+        let guild_id = cx.invocation.guild_id().get();
+        let queue = cx.state.get_or_create_queue(guild_id).await;
+
+        let upcoming = {
+            let mut queue_lock = queue.lock().await;
+            if queue_lock.len() <= 2 {
+                return "Not enough songs queued to shuffle.".into();
+            }
+            // Leave the playing track at index 0 in place
+            shuffle(&mut queue_lock[1..]);
+            queue_lock.len() - 1
+        };
+
+        cx.state.prefetcher.prefetch(guild_id, &queue).await;
+        format!("🔀 Shuffled {} upcoming song(s)", upcoming).into()
+    }
+}
+
+struct Clear;
+
+#[async_trait]
+impl Command for Clear {
+    fn name(&self) -> &'static str {
+        "clear"
+    }
+
+    fn description(&self) -> &'static str {
+        "Remove all upcoming songs but keep the current one playing"
+    }
+
+    async fn run(&self, cx: CommandContext<'_>) -> Reply {
+        let guild_id = cx.invocation.guild_id().get();
+        let queue = cx.state.get_or_create_queue(guild_id).await;
+
+        let cleared = {
+            let mut queue_lock = queue.lock().await;
+            let cleared = queue_lock.len().saturating_sub(1);
+            queue_lock.truncate(1);
+            cleared
+        };
+
+        cx.state.prefetcher.clear(guild_id).await;
+        format!("🧹 Cleared {} upcoming song(s)", cleared).into()
+    }
+}
+
+struct SkipTo;
+
+#[async_trait]
+impl Command for SkipTo {
+    fn name(&self) -> &'static str {
+        "skipto"
+    }
+
+    fn description(&self) -> &'static str {
+        "Skip straight to a song in the queue"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &["jump"]
+    }
+
+    fn args(&self) -> &'static [ArgSpec] {
+        &[ArgSpec {
+            name: "position",
+            description: "Queue position as shown by !queue",
+            kind: ArgKind::Integer,
+            required: true,
+        }]
+    }
+
+    async fn run(&self, cx: CommandContext<'_>) -> Reply {
+        let guild_id = cx.invocation.guild_id();
+        let queue = cx.state.get_or_create_queue(guild_id.get()).await;
+        let player = cx.player().await;
+
+        if !player.is_active().await {
+            return "Nothing is playing!".into();
+        }
+
+        let loop_mode = player.loop_mode().await;
+
+        let title = {
+            let mut queue_lock = queue.lock().await;
+            let index = match upcoming_index(cx.args.get_int("position"), queue_lock.len()) {
+                Ok(index) => index,
+                Err(e) => return e.into(),
+            };
+            if loop_mode == LoopMode::Queue {
+                // Rotate the target to the head, the current track and the
+                // skipped entries keep their order at the back. The head is no
+                // longer the current track, so skipping just plays the target.
+                queue_lock.rotate_left(index);
+                queue_lock[0].display_title().to_string()
+            } else {
+                // Take out everything between the current track and the
+                // target, skipping then removes the current one
+                queue_lock.drain(1..index);
+                queue_lock[1].display_title().to_string()
+            }
+        };
+
+        match player.skip().await {
+            Ok(()) => format!("⏭️ Skipping to **{}**", title),
+            Err(e) => e,
+        }
+        .into()
+    }
+}
@@ -0,0 +1,120 @@
+use serenity::{
+    async_trait,
+    builder::{
+        CreateActionRow, CreateEmbed, CreateEmbedFooter, CreateSelectMenu, CreateSelectMenuKind,
+        CreateSelectMenuOption,
+    },
+};
+use std::time::Duration;
+
+use super::{ArgKind, ArgSpec, Command, CommandContext, Registry, Reply};
+use crate::music::search;
+use crate::queue::format_optional_duration;
+
+pub fn register(registry: &mut Registry) {
+    registry.register(Search);
+}
+
+/// Results offered by `!search`
+const SEARCH_RESULTS: usize = 5;
+
+/// Prefix of the custom id of the `!search` result menu, followed by the id of
+/// the user who searched
+pub const SEARCH_MENU_PREFIX: &str = "search:";
+
+/// How long the result menu can be used
+pub const SEARCH_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Longest label, description and value Discord accepts for a menu option
+const OPTION_TEXT_LIMIT: usize = 100;
+
+struct Search;
+
+#[async_trait]
+impl Command for Search {
+    fn name(&self) -> &'static str {
+        "search"
+    }
+
+    fn description(&self) -> &'static str {
+        "Search for a song and pick which result to play"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &["find"]
+    }
+
+    fn args(&self) -> &'static [ArgSpec] {
+        &[ArgSpec {
+            name: "query",
+            description: "What to search for, sc: searches SoundCloud",
+            kind: ArgKind::String,
+            required: true,
+        }]
+    }
+
+    async fn run(&self, cx: CommandContext<'_>) -> Reply {
+        let query = cx.args.get("query").unwrap_or_default();
+
+        let results = match search(query, SEARCH_RESULTS).await {
+            Ok(results) => results,
+            Err(e) => return format!("❌ Search failed: {}", e).into(),
+        };
+        // The menu passes the pick on as the option value
+        let results: Vec<_> = results
+            .into_iter()
+            .filter(|result| result.query.len() <= OPTION_TEXT_LIMIT)
+            .collect();
+        if results.is_empty() {
+            return format!("No results for `{}`.", query).into();
+        }
+
+        let mut lines = Vec::new();
+        let mut options = Vec::new();
+        for (i, result) in results.iter().enumerate() {
+            let title = result.metadata.title.as_deref().unwrap_or(&result.query);
+            let channel = result.metadata.channel.as_deref().unwrap_or("unknown");
+            let duration = format_optional_duration(result.metadata.duration);
+
+            lines.push(format!(
+                "`{}.` **{}** by {} `[{}]`",
+                i + 1,
+                title,
+                channel,
+                duration
+            ));
+            options.push(
+                CreateSelectMenuOption::new(
+                    truncate(&format!("{}. {}", i + 1, title)),
+                    result.query.clone(),
+                )
+                .description(truncate(&format!("{} • {}", channel, duration))),
+            );
+        }
+
+        let embed = CreateEmbed::new()
+            .title(format!("🔎 Results for \"{}\"", query))
+            .description(lines.join("\n"))
+            .footer(CreateEmbedFooter::new(format!(
+                "Pick a song below within {}s",
+                SEARCH_TIMEOUT.as_secs()
+            )));
+        let menu = CreateSelectMenu::new(
+            format!("{}{}", SEARCH_MENU_PREFIX, cx.invocation.user_id),
+            CreateSelectMenuKind::String { options },
+        )
+        .placeholder("Pick a song to play");
+
+        Reply::embed(embed).components(vec![CreateActionRow::SelectMenu(menu)])
+    }
+}
+
+/// Cut text to what a menu option can show
+fn truncate(text: &str) -> String {
+    if text.chars().count() <= OPTION_TEXT_LIMIT {
+        return text.to_string();
+    }
+    let mut cut: String = text.chars().take(OPTION_TEXT_LIMIT - 1).collect();
+    cut.push('…');
+    cut
+}
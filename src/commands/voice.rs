@@ -0,0 +1,70 @@
+use serenity::async_trait;
+
+use super::{
+    current_call, user_voice_channel, voice_manager, Command, CommandContext, Registry, Reply,
+};
+
+pub fn register(registry: &mut Registry) {
+    registry.register(Join);
+    registry.register(Leave);
+}
+
+struct Join;
+
+#[async_trait]
+impl Command for Join {
+    fn name(&self) -> &'static str {
+        "join"
+    }
+
+    fn description(&self) -> &'static str {
+        "Join your voice channel"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &["connect"]
+    }
+
+    async fn run(&self, cx: CommandContext<'_>) -> Reply {
+        let connect_to = match user_voice_channel(cx.ctx, cx.invocation) {
+            Some(channel) => channel,
+            None => return "You need to be in a voice channel!".into(),
+        };
+
+        let manager = voice_manager(cx.ctx).await;
+        let _ = manager.join(cx.invocation.guild_id(), connect_to).await;
+        let player = cx.player().await;
+        player.set_text_channel(cx.invocation.channel_id).await;
+        player.check_idle().await;
+        format!("Joined <#{}>", connect_to).into()
+    }
+}
+
+struct Leave;
+
+#[async_trait]
+impl Command for Leave {
+    fn name(&self) -> &'static str {
+        "leave"
+    }
+
+    fn description(&self) -> &'static str {
+        "Leave the voice channel"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &["disconnect"]
+    }
+
+    async fn run(&self, cx: CommandContext<'_>) -> Reply {
+        let guild_id = cx.invocation.guild_id();
+        if current_call(cx.ctx, guild_id).await.is_none() {
+            return "I'm not in a voice channel!".into();
+        }
+
+        cx.state
+            .teardown_guild(voice_manager(cx.ctx).await, guild_id)
+            .await;
+        "Left the voice channel 👋".into()
+    }
+}
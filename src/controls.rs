@@ -0,0 +1,189 @@
+use serenity::{
+    builder::{CreateInteractionResponse, CreateInteractionResponseMessage},
+    model::{
+        application::{ComponentInteraction, ComponentInteractionDataKind},
+        Timestamp,
+    },
+    prelude::*,
+};
+
+use crate::commands::{
+    current_call, user_voice_channel, voice_manager, Invocation, RawArgs, Registry, Reply,
+    PAGE_BUTTON_PREFIX, SEARCH_MENU_PREFIX, SEARCH_TIMEOUT,
+};
+use crate::now_playing::BUTTON_PREFIX;
+use crate::state::BotState;
+
+/// Volume change per click of the volume buttons, in percent
+const VOLUME_STEP: i32 = 10;
+
+/// Handle a click on a message component the bot posted
+pub async fn handle(
+    ctx: &Context,
+    state: &BotState,
+    registry: &Registry,
+    component: &ComponentInteraction,
+) {
+    let Some(guild_id) = component.guild_id else {
+        return;
+    };
+
+    let invocation = Invocation {
+        guild_id: Some(guild_id),
+        channel_id: component.channel_id,
+        user_id: component.user.id,
+        permissions: component.member.as_ref().and_then(|m| m.permissions),
+    };
+
+    let custom_id = component.data.custom_id.as_str();
+    if let Some(action) = custom_id.strip_prefix(BUTTON_PREFIX) {
+        panel_action(ctx, state, registry, component, &invocation, action).await;
+    } else if let Some(page) = custom_id.strip_prefix(PAGE_BUTTON_PREFIX) {
+        turn_queue_page(ctx, state, registry, component, &invocation, page).await;
+    } else if let Some(searcher) = custom_id.strip_prefix(SEARCH_MENU_PREFIX) {
+        pick_search_result(ctx, state, registry, component, &invocation, searcher).await;
+    }
+}
+
+/// Run the action of a now-playing control panel button.
+///
+/// Buttons run the same commands as their text counterparts, the reply is
+/// shown only to the user who clicked. The player updates the embed itself.
+async fn panel_action(
+    ctx: &Context,
+    state: &BotState,
+    registry: &Registry,
+    component: &ComponentInteraction,
+    invocation: &Invocation,
+    action: &str,
+) {
+    let guild_id = invocation.guild_id();
+
+    let player = state
+        .get_or_create_player(voice_manager(ctx).await, ctx.http.clone(), guild_id)
+        .await;
+
+    let reply: Reply = if !player.is_panel(component.message.id).await {
+        "This track has already ended.".into()
+    } else if !in_bot_channel(ctx, invocation).await {
+        "You need to be in my voice channel to use these controls!".into()
+    } else {
+        let command = match action {
+            "pause" if player.is_paused().await => Some("resume"),
+            "pause" => Some("pause"),
+            "skip" | "stop" | "loop" | "shuffle" => Some(action),
+            _ => None,
+        };
+
+        match (command, action) {
+            (Some(command), _) => registry
+                .dispatch(ctx, state, invocation, command, RawArgs::Text(None))
+                .await
+                .unwrap_or_else(|| "Unknown command".into()),
+            (None, "volume_up") => {
+                format!("🔊 Volume: {}%", player.change_volume(VOLUME_STEP).await).into()
+            }
+            (None, "volume_down") => {
+                format!("🔉 Volume: {}%", player.change_volume(-VOLUME_STEP).await).into()
+            }
+            _ => return,
+        }
+    };
+
+    let response = reply.into_response_message().ephemeral(true);
+    let _ = component
+        .create_response(&ctx.http, CreateInteractionResponse::Message(response))
+        .await;
+}
+
+/// Show another page of a `!queue` reply by editing it in place
+async fn turn_queue_page(
+    ctx: &Context,
+    state: &BotState,
+    registry: &Registry,
+    component: &ComponentInteraction,
+    invocation: &Invocation,
+    page: &str,
+) {
+    let Some(reply) = registry
+        .dispatch(ctx, state, invocation, "queue", RawArgs::Text(Some(page)))
+        .await
+    else {
+        return;
+    };
+
+    let _ = component
+        .create_response(
+            &ctx.http,
+            CreateInteractionResponse::UpdateMessage(reply.into_response_message()),
+        )
+        .await;
+}
+
+/// Play the song picked from a `!search` menu, replacing the menu with the
+/// reply. Only the user who searched can pick, and only until it times out.
+async fn pick_search_result(
+    ctx: &Context,
+    state: &BotState,
+    registry: &Registry,
+    component: &ComponentInteraction,
+    invocation: &Invocation,
+    searcher: &str,
+) {
+    let age = Timestamp::now().unix_timestamp() - component.message.timestamp.unix_timestamp();
+    if age > SEARCH_TIMEOUT.as_secs() as i64 {
+        let expired = CreateInteractionResponseMessage::new()
+            .content("⌛ This search has expired, run `!search` again.")
+            .embeds(Vec::new())
+            .components(Vec::new());
+        let _ = component
+            .create_response(&ctx.http, CreateInteractionResponse::UpdateMessage(expired))
+            .await;
+        return;
+    }
+
+    if searcher != invocation.user_id.to_string() {
+        let response = CreateInteractionResponseMessage::new()
+            .content(format!("Only <@{}> can pick from this search.", searcher))
+            .ephemeral(true);
+        let _ = component
+            .create_response(&ctx.http, CreateInteractionResponse::Message(response))
+            .await;
+        return;
+    }
+
+    let ComponentInteractionDataKind::StringSelect { values } = &component.data.kind else {
+        return;
+    };
+    let Some(choice) = values.first() else {
+        return;
+    };
+
+    // Downloading can take longer than Discord waits for a response
+    let _ = component
+        .create_response(&ctx.http, CreateInteractionResponse::Acknowledge)
+        .await;
+
+    let Some(reply) = registry
+        .dispatch(ctx, state, invocation, "play", RawArgs::Text(Some(choice)))
+        .await
+    else {
+        return;
+    };
+    let replaces_embed = reply.embed.is_some();
+    let mut edit = reply.into_edit_response();
+    if !replaces_embed {
+        edit = edit.embeds(Vec::new());
+    }
+    let _ = component.edit_response(&ctx.http, edit).await;
+}
+
+/// Whether the invoking user is in the voice channel the bot is connected to
+async fn in_bot_channel(ctx: &Context, invocation: &Invocation) -> bool {
+    let Some(call) = current_call(ctx, invocation.guild_id()).await else {
+        return false;
+    };
+    let bot_channel = call.lock().await.current_channel();
+
+    bot_channel.is_some() && bot_channel == user_voice_channel(ctx, invocation).map(Into::into)
+}
@@ -1,71 +1,82 @@
 use serenity::async_trait;
-use songbird::{
-    Call, EventContext, EventHandler as VoiceEventHandler, TrackEvent,
-};
-use std::path::PathBuf;
-use sync::Arc;
+use songbird::{tracks::PlayMode, EventContext, EventHandler as VoiceEventHandler};
 
-use crate::cleanup::{cleanup_guild_temp_files, ActiveFiles};
-use crate::music::create_source;
-use crate::queue::Queue;
+use crate::player::Player;
 
 /// Track end notification handler
 pub struct TrackEndNotifier {
-    pub guild_id: songbird::model::id::GuildId,
-    pub call: Arc<tokio::sync::Mutex<Call>>,
-    pub queue: Queue,
-    pub active_files: ActiveFiles,
-    pub downloaded_file: Option<PathBuf>,
+    pub player: Player,
+    /// Playback the handler was registered for
+    pub play_id: u64,
+    /// Queue entry the track was started for
+    pub track_id: u64,
 }
 
 #[async_trait]
 impl VoiceEventHandler for TrackEndNotifier {
-    async fn act(&self, _ctx: &EventContext<'_>) -> Option<songbird::Event> {
-        // Remove this file from active set
-        if let Some(ref file_path) = self.downloaded_file {
-            let mut active = self.active_files.lock().await;
-            if let Some(files) = active.get_mut(&self.guild_id.0) {
-                files.remove(file_path);
+    async fn act(&self, ctx: &EventContext<'_>) -> Option<songbird::Event> {
+        // Songbird also fires End for failed tracks, those are handled by TrackErrorNotifier
+        if let EventContext::Track(tracks) = ctx {
+            if tracks
+                .iter()
+                .any(|(state, _)| matches!(state.playing, PlayMode::Errored(_)))
+            {
+                return None;
             }
         }
 
-        // Clean up old files for this guild (only inactive ones)
-        cleanup_guild_temp_files(self.guild_id.0, &self.active_files).await;
+        self.player.on_track_end(self.play_id, self.track_id).await;
 
-        // Remove the finished song from queue
-        let mut queue = self.queue.lock().await;
-        if !queue.is_empty() {
-            queue.remove(0);
-        }
+        None
+    }
+}
 
-        // Play next song if available
-        if !queue.is_empty() {
-            let next_url = queue[0].clone();
-            drop(queue); // Release lock before async operation
+/// Fires shortly before a track ends to start the crossfade into the next one
+pub struct CrossfadeNotifier {
+    pub player: Player,
+    /// Playback the handler was registered for
+    pub play_id: u64,
+    /// Queue entry the track was started for
+    pub track_id: u64,
+}
 
-            if let Ok((source, file_path)) = create_source(&self.guild_id.0, &next_url).await {
-                // Mark new file as active
-                if let Some(ref path) = file_path {
-                    let mut active = self.active_files.lock().await;
-                    active.entry(self.guild_id.0).or_default().insert(path.clone());
-                }
+#[async_trait]
+impl VoiceEventHandler for CrossfadeNotifier {
+    async fn act(&self, _ctx: &EventContext<'_>) -> Option<songbird::Event> {
+        // Fire again later if the track isn't that far yet
+        self.player
+            .on_crossfade(self.play_id, self.track_id)
+            .await
+            .map(songbird::Event::Delayed)
+    }
+}
 
-                let mut call = self.call.lock().await;
-                let handle = call.play_input(source);
+/// Track playback error handler
+pub struct TrackErrorNotifier {
+    pub player: Player,
+    /// Playback the handler was registered for
+    pub play_id: u64,
+    /// Queue entry the track was started for
+    pub track_id: u64,
+}
 
-                // Add track end handler for the next song
-                let _ = handle.add_event(
-                    songbird::Event::Track(TrackEvent::End),
-                    TrackEndNotifier {
-                        guild_id: self.guild_id,
-                        call: self.call.clone(),
-                        queue: self.queue.clone(),
-                        active_files: self.active_files.clone(),
-                        downloaded_file: file_path,
-                    },
-                );
+#[async_trait]
+impl VoiceEventHandler for TrackErrorNotifier {
+    async fn act(&self, ctx: &EventContext<'_>) -> Option<songbird::Event> {
+        let reason = match ctx {
+            EventContext::Track(tracks) => {
+                tracks.iter().find_map(|(state, _)| match &state.playing {
+                    PlayMode::Errored(e) => Some(e.to_string()),
+                    _ => None,
+                })
             }
+            _ => None,
         }
+        .unwrap_or_else(|| "unknown playback error".to_string());
+
+        self.player
+            .on_track_error(self.play_id, self.track_id, reason)
+            .await;
 
         None
     }
@@ -0,0 +1,204 @@
+/// Preset audio effect selectable with `!filter`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Effect {
+    BassBoost,
+    Nightcore,
+    Vaporwave,
+    /// "8D" audio, panning slowly around the listener
+    Rotate,
+    /// Vocal removal by cancelling the centre channel
+    Karaoke,
+}
+
+impl Effect {
+    pub const ALL: [Effect; 5] = [
+        Effect::BassBoost,
+        Effect::Nightcore,
+        Effect::Vaporwave,
+        Effect::Rotate,
+        Effect::Karaoke,
+    ];
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_lowercase().as_str() {
+            "bassboost" | "bass" => Some(Self::BassBoost),
+            "nightcore" => Some(Self::Nightcore),
+            "vaporwave" => Some(Self::Vaporwave),
+            "8d" | "rotate" => Some(Self::Rotate),
+            "karaoke" => Some(Self::Karaoke),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::BassBoost => "bassboost",
+            Self::Nightcore => "nightcore",
+            Self::Vaporwave => "vaporwave",
+            Self::Rotate => "8d",
+            Self::Karaoke => "karaoke",
+        }
+    }
+
+    /// ffmpeg audio filter implementing the effect
+    fn ffmpeg_filter(self) -> &'static str {
+        match self {
+            Self::BassBoost => "bass=g=10",
+            Self::Nightcore => "aresample=48000,asetrate=48000*1.25,aresample=48000",
+            Self::Vaporwave => "aresample=48000,asetrate=48000*0.8,aresample=48000",
+            Self::Rotate => "apulsator=hz=0.125",
+            Self::Karaoke => "pan=stereo|c0=c0-c1|c1=c1-c0",
+        }
+    }
+
+    /// Factor the effect speeds playback up by
+    pub fn tempo(self) -> f64 {
+        match self {
+            Self::Nightcore => 1.25,
+            Self::Vaporwave => 0.8,
+            _ => 1.0,
+        }
+    }
+}
+
+/// One band of the custom equalizer
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EqBand {
+    /// Centre frequency in Hz
+    pub frequency: u32,
+    /// Gain in dB
+    pub gain: f32,
+}
+
+impl EqBand {
+    /// Parse a band written as `<frequency>:<gain>`, e.g. `60:+6` or `4000:-3`
+    pub fn parse(value: &str) -> Option<Self> {
+        let (frequency, gain) = value.split_once(':')?;
+        let band = Self {
+            frequency: frequency.parse().ok()?,
+            gain: gain.trim_start_matches('+').parse().ok()?,
+        };
+        let valid = (20..=20_000).contains(&band.frequency) && (-20.0..=20.0).contains(&band.gain);
+        valid.then_some(band)
+    }
+}
+
+/// Slowest and fastest playback speed
+pub const SPEED_RANGE: std::ops::RangeInclusive<f64> = 0.5..=2.0;
+
+/// Largest pitch shift in semitones, either way
+pub const PITCH_LIMIT: i32 = 12;
+
+/// Audio effects of a guild, applied by decoding the track through ffmpeg
+#[derive(Clone, Debug, PartialEq)]
+pub struct AudioFilters {
+    pub effects: Vec<Effect>,
+    pub eq: Vec<EqBand>,
+    /// Playback speed factor, without changing the pitch
+    pub speed: f64,
+    /// Pitch shift in semitones, without changing the speed
+    pub pitch: i32,
+}
+
+impl Default for AudioFilters {
+    fn default() -> Self {
+        Self {
+            effects: Vec::new(),
+            eq: Vec::new(),
+            speed: 1.0,
+            pitch: 0,
+        }
+    }
+}
+
+impl AudioFilters {
+    /// Whether no effect or equalizer band is on. Speed and pitch are separate.
+    pub fn is_empty(&self) -> bool {
+        self.effects.is_empty() && self.eq.is_empty()
+    }
+
+    /// Speed tracks actually play at, with the effects that change it
+    pub fn effective_speed(&self) -> f64 {
+        self.effects
+            .iter()
+            .fold(self.speed, |speed, effect| speed * effect.tempo())
+    }
+
+    /// Filters changing speed and pitch independently: the sample rate trick
+    /// shifts both, then `atempo` puts the speed where it should be
+    fn tempo_filters(&self) -> Vec<String> {
+        let mut filters = Vec::new();
+        let mut tempo = self.speed;
+        if self.pitch != 0 {
+            let ratio = 2f64.powf(self.pitch as f64 / 12.0);
+            filters.push(format!(
+                "aresample=48000,asetrate=48000*{:.6},aresample=48000",
+                ratio
+            ));
+            tempo /= ratio;
+        }
+
+        // atempo only takes factors from 0.5 to 2 at a time
+        while tempo > 2.0 {
+            filters.push("atempo=2".to_string());
+            tempo /= 2.0;
+        }
+        while tempo < 0.5 {
+            filters.push("atempo=0.5".to_string());
+            tempo /= 0.5;
+        }
+        if (tempo - 1.0).abs() > 1e-6 {
+            filters.push(format!("atempo={:.6}", tempo));
+        }
+        filters
+    }
+
+    /// Turn an effect on, or off if it is already on. Returns whether it is on.
+    pub fn toggle(&mut self, effect: Effect) -> bool {
+        if self.effects.contains(&effect) {
+            self.effects.retain(|e| *e != effect);
+            false
+        } else {
+            self.effects.push(effect);
+            true
+        }
+    }
+
+    /// The `-af` filter graph, `None` when nothing is enabled
+    pub fn ffmpeg_filter(&self) -> Option<String> {
+        let filters: Vec<String> = self
+            .tempo_filters()
+            .into_iter()
+            .chain(
+                self.effects
+                    .iter()
+                    .map(|effect| effect.ffmpeg_filter().to_string()),
+            )
+            .chain(
+                self.eq
+                    .iter()
+                    .map(|band| format!("equalizer=f={}:t=q:w=1:g={}", band.frequency, band.gain)),
+            )
+            .collect();
+
+        (!filters.is_empty()).then(|| filters.join(","))
+    }
+
+    /// Short description for command replies
+    pub fn describe(&self) -> String {
+        if self.is_empty() {
+            return "none".to_string();
+        }
+
+        let mut parts: Vec<String> = self.effects.iter().map(|e| e.name().to_string()).collect();
+        if !self.eq.is_empty() {
+            let bands: Vec<String> = self
+                .eq
+                .iter()
+                .map(|band| format!("{}Hz {:+}dB", band.frequency, band.gain))
+                .collect();
+            parts.push(format!("eq ({})", bands.join(", ")));
+        }
+        parts.join(", ")
+    }
+}
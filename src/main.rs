@@ -1,265 +1,203 @@
+mod cache;
 mod cleanup;
+mod commands;
+mod controls;
 mod events;
+mod filters;
 mod music;
+mod now_playing;
+mod player;
+mod prefetch;
 mod queue;
+mod resolver;
+mod settings;
 mod shutdown;
+mod sources;
+mod state;
 
-use cleanup::{ActiveFiles, cleanup_all_temp_files, cleanup_guild_temp_files};
-use events::TrackEndNotifier;
-use music::create_source;
-use queue::{Queue, QueueMap};
-use shutdown::ShutdownHandler;
+use cleanup::cleanup_all_temp_files;
+use commands::{current_call, voice_manager, Invocation, RawArgs, Registry};
+use state::BotState;
 
 use serenity::{
     async_trait,
-    model::{channel::Message, gateway::Ready, id::GuildId},
+    model::{
+        application::{Command, Interaction},
+        channel::Message,
+        gateway::Ready,
+        guild::{Guild, UnavailableGuild},
+        id::GuildId,
+        voice::VoiceState,
+    },
     prelude::*,
 };
-use songbird::{Call, EventContext, TrackEvent};
-use std::{sync::Arc, time::Duration};
-use tokio::sync::Mutex;
+use songbird::SerenityInit;
+use std::time::Duration;
 
 struct Handler {
-    queues: QueueMap,
-    active_files: ActiveFiles,
+    state: BotState,
+    registry: Registry,
 }
 
 impl Handler {
     fn new() -> Self {
         Self {
-            queues: Arc::new(Mutex::new(HashMap::new())),
-            active_files: Arc::new(Mutex::new(HashMap::new())),
+            state: BotState::new(),
+            registry: Registry::new(),
         }
     }
-
-    async fn get_or_create_queue(&self, guild_id: u64) -> Queue {
-        let mut queues = self.queues.lock().await;
-        queues
-            .entry(guild_id)
-            .or_insert_with(|| Arc::new(Mutex::new(Vec::new())))
-            .clone()
-    }
 }
 
 #[async_trait]
 impl EventHandler for Handler {
     async fn message(&self, ctx: Context, msg: Message) {
-        let content = msg.content.trim();
-
-        if !content.starts_with('!') {
+        // Never answer other bots, or ourselves
+        if msg.author.bot {
             return;
         }
 
-        let args: Vec<&str> = content.splitn(2, ' ').collect();
-        let guild_id = match msg.guild_id {
-            Some(id) => id,
+        let content = msg.content.trim();
+
+        let command = match content.strip_prefix('!') {
+            Some(command) => command,
             None => return,
         };
 
-        match args[0] {
-            "!join" => {
-                let channel_id = msg
-                    .member
-                    .as_ref()
-                    .and_then(|m| m.voice.as_ref())
-                    .and_then(|v| v.channel_id);
-
-                let connect_to = match channel_id {
-                    Some(channel) => channel,
-                    None => {
-                        let _ = msg.reply(&ctx.http, "You need to be in a voice channel!").await;
-                        return;
-                    }
-                };
-
-                let manager = songbird::get(&ctx).await.unwrap();
-                let _ = manager.join(guild_id, connect_to).await;
-                let _ = msg.reply(&ctx.http, format!("Joined <#{}>", connect_to)).await;
-            }
-
-            "!play" => {
-                if args.len() < 2 {
-                    let _ = msg
-                        .reply(&ctx.http, "Usage: !play <song name or YouTube URL>")
-                        .await;
-                    return;
-                }
+        let args: Vec<&str> = command.splitn(2, ' ').collect();
 
-                let query = args[1].to_string();
-                let queue = self.get_or_create_queue(guild_id.0).await;
-
-                // Ensure bot is in voice channel
-                let channel_id = msg
-                    .member
-                    .as_ref()
-                    .and_then(|m| m.voice.as_ref())
-                    .and_then(|v| v.channel_id);
-
-                let connect_to = match channel_id {
-                    Some(channel) => channel,
-                    None => {
-                        let _ = msg.reply(&ctx.http, "You need to be in a voice channel!").await;
-                        return;
-                    }
-                };
-
-                let manager = songbird::get(&ctx).await.unwrap();
-                let has_handler = manager.get(guild_id).is_some();
-
-                if !has_handler {
-                    let _ = manager.join(guild_id, connect_to).await;
-                }
+        let invocation = Invocation {
+            guild_id: msg.guild_id,
+            channel_id: msg.channel_id,
+            user_id: msg.author.id,
+            permissions: msg.author_permissions(&ctx.cache),
+        };
 
-                // Add to queue
-                let mut queue_lock = queue.lock().await;
-                queue_lock.push(query.clone());
-                let queue_len = queue_lock.len();
-                drop(queue_lock);
-
-                if queue_len == 1 {
-                    // Play immediately if queue was empty
-                    if let Some(handler_lock) = manager.get(guild_id) {
-                        let mut handler = handler_lock.lock().await;
-
-                        match create_source(&guild_id.0, &query).await {
-                            Ok((source, file_path)) => {
-                                // Mark file as active
-                                if let Some(ref path) = file_path {
-                                    let mut active = self.active_files.lock().await;
-                                    active.entry(guild_id.0).or_default().insert(path.clone());
-                                }
-
-                                let handle = handler.play_input(source);
-
-                                // Add event handler for when track ends
-                                let _ = handle.add_event(
-                                    songbird::Event::Track(TrackEvent::End),
-                                    TrackEndNotifier {
-                                        guild_id,
-                                        call: handler_lock.clone(),
-                                        queue: queue.clone(),
-                                        active_files: self.active_files.clone(),
-                                        downloaded_file: file_path,
-                                    },
-                                );
-
-                                let _ = msg.reply(&ctx.http, "🎵 Now playing!").await;
-                            }
-                            Err(e) => {
-                                let _ = msg
-                                    .reply(&ctx.http, format!("Error playing song: {}", e))
-                                    .await;
-                                queue.lock().await.remove(0);
-                            }
-                        }
-                    }
-                } else {
-                    let _ = msg
-                        .reply(&ctx.http, format!("Added to queue (position {})", queue_len))
-                        .await;
-                }
-            }
+        if let Some(reply) = self
+            .registry
+            .dispatch(
+                &ctx,
+                &self.state,
+                &invocation,
+                args[0],
+                RawArgs::Text(args.get(1).copied()),
+            )
+            .await
+        {
+            let message = reply.into_message().reference_message(&msg);
+            let _ = msg.channel_id.send_message(&ctx.http, message).await;
+        }
+    }
 
-            "!pause" => {
-                let manager = songbird::get(&ctx).await.unwrap();
-                if let Some(handler_lock) = manager.get(guild_id) {
-                    let handler = handler_lock.lock().await;
-                    let _ = handler.queue().pause();
-                    let _ = msg.reply(&ctx.http, "Paused ⏸️").await;
-                }
+    async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
+        let command = match interaction {
+            Interaction::Command(command) => command,
+            Interaction::Component(component) => {
+                controls::handle(&ctx, &self.state, &self.registry, &component).await;
+                return;
             }
+            _ => return,
+        };
 
-            "!resume" => {
-                let manager = songbird::get(&ctx).await.unwrap();
-                if let Some(handler_lock) = manager.get(guild_id) {
-                    let handler = handler_lock.lock().await;
-                    let _ = handler.queue().resume();
-                    let _ = msg.reply(&ctx.http, "Resumed ▶️").await;
-                }
-            }
+        // Commands like /play can take longer than Discord's 3 second reply window
+        let _ = command.defer(&ctx.http).await;
 
-            "!skip" => {
-                let manager = songbird::get(&ctx).await.unwrap();
-                if let Some(handler_lock) = manager.get(guild_id) {
-                    let handler = handler_lock.lock().await;
-                    let _ = handler.queue().skip();
-                    let _ = msg.reply(&ctx.http, "Skipped ⏭️").await;
-                }
-            }
+        let invocation = Invocation {
+            guild_id: command.guild_id,
+            channel_id: command.channel_id,
+            user_id: command.user.id,
+            permissions: command.member.as_ref().and_then(|m| m.permissions),
+        };
 
-            "!stop" => {
-                let queue = self.get_or_create_queue(guild_id.0).await;
-                queue.lock().await.clear();
+        let reply = self
+            .registry
+            .dispatch(
+                &ctx,
+                &self.state,
+                &invocation,
+                &command.data.name,
+                RawArgs::Options(&command.data.options),
+            )
+            .await
+            .unwrap_or_else(|| "Unknown command".into());
+
+        let _ = command
+            .edit_response(&ctx.http, reply.into_edit_response())
+            .await;
+    }
 
-                let manager = songbird::get(&ctx).await.unwrap();
-                if let Some(handler_lock) = manager.get(guild_id) {
-                    let handler = handler_lock.lock().await;
-                    handler.queue().stop();
-                }
-                let _ = msg.reply(&ctx.http, "Stopped and cleared queue ⏹️").await;
-            }
+    async fn voice_state_update(&self, ctx: Context, _old: Option<VoiceState>, new: VoiceState) {
+        let Some(guild_id) = new.guild_id else {
+            return;
+        };
 
-            "!queue" => {
-                let queue = self.get_or_create_queue(guild_id.0).await;
-                let queue_lock = queue.lock().await;
-
-                if queue_lock.is_empty() {
-                    let _ = msg.reply(&ctx.http, "Queue is empty!").await;
-                } else {
-                    let queue_list: String = queue_lock
-                        .iter()
-                        .enumerate()
-                        .map(|(i, song)| {
-                            if i == 0 {
-                                format!("▶️ {}", song)
-                            } else {
-                                format!("{}. {}", i, song)
-                            }
-                        })
-                        .collect::<Vec<_>>()
-                        .join("\n");
-
-                    let _ = msg
-                        .reply(&ctx.http, format!("**Queue:**\n{}", queue_list))
-                        .await;
-                }
-            }
+        // Disconnected from voice, by `!leave`, the idle timer or a moderator
+        if new.user_id == ctx.cache.current_user().id && new.channel_id.is_none() {
+            self.state
+                .teardown_guild(voice_manager(&ctx).await, guild_id)
+                .await;
+            return;
+        }
 
-            "!leave" => {
-                let manager = songbird::get(&ctx).await.unwrap();
-                if manager.get(guild_id).is_some() {
-                    let _ = manager.remove(guild_id).await;
-                    let _ = msg.reply(&ctx.http, "Left the voice channel 👋").await;
-                }
-            }
+        let Some(call) = current_call(&ctx, guild_id).await else {
+            return;
+        };
+        let Some(bot_channel) = call.lock().await.current_channel() else {
+            return;
+        };
 
-            "!shutdown" => {
-                // Check if user has permission to shutdown
-                if let Some(member) = &msg.member {
-                    if let Ok(permissions) = member.permissions(&ctx.http).await {
-                        if permissions.administrator() {
-                            let _ = msg.reply(&ctx.http, "🛑 Initiating graceful shutdown...").await;
-                            // Trigger graceful shutdown
-                            let shutdown = ShutdownHandler::new(self.queues.clone());
-                            tokio::spawn(async move {
-                                shutdown.run().await;
-                            });
-                        } else {
-                            let _ = msg.reply(&ctx.http, "❌ You need administrator permissions to shut down the bot.").await;
-                        }
-                    }
-                }
-            }
+        // Pause and start the leave timer when the last listener leaves
+        let listeners = human_listeners(&ctx, guild_id, bot_channel);
+        self.state
+            .get_or_create_player(voice_manager(&ctx).await, ctx.http.clone(), guild_id)
+            .await
+            .on_listeners_changed(listeners)
+            .await;
+    }
 
-            _ => {}
+    async fn guild_delete(&self, ctx: Context, incomplete: UnavailableGuild, _full: Option<Guild>) {
+        // Unavailable guilds are an outage, not a removal
+        if incomplete.unavailable {
+            return;
         }
+
+        self.state
+            .teardown_guild(voice_manager(&ctx).await, incomplete.id)
+            .await;
     }
 
-    async fn ready(&self, _: Context, ready: Ready) {
+    async fn ready(&self, ctx: Context, ready: Ready) {
         println!("{} is connected!", ready.user.name);
+
+        // Register slash commands alongside the ! prefix commands
+        match Command::set_global_commands(&ctx.http, self.registry.slash_commands()).await {
+            Ok(commands) => println!("📋 Registered {} slash command(s)", commands.len()),
+            Err(e) => eprintln!("❌ Failed to register slash commands: {}", e),
+        }
     }
 }
 
+/// Number of non-bot members in the given voice channel
+fn human_listeners(ctx: &Context, guild_id: GuildId, channel: songbird::id::ChannelId) -> usize {
+    let Some(guild) = ctx.cache.guild(guild_id) else {
+        return 0;
+    };
+
+    guild
+        .voice_states
+        .values()
+        .filter(|state| state.channel_id.map(Into::into) == Some(channel))
+        .filter(|state| {
+            let is_bot = state
+                .member
+                .as_ref()
+                .or_else(|| guild.members.get(&state.user_id))
+                .map(|member| member.user.bot)
+                .unwrap_or(false);
+            !is_bot && state.user_id != ctx.cache.current_user().id
+        })
+        .count()
+}
+
 #[tokio::main]
 async fn main() {
     let token = std::env::var("DISCORD_TOKEN").expect("Expected DISCORD_TOKEN in environment");
@@ -269,8 +207,8 @@ async fn main() {
         | GatewayIntents::GUILD_VOICE_STATES;
 
     let handler = Handler::new();
-    let queues = handler.queues.clone();
-    let active_files = handler.active_files.clone();
+    let shutdown = handler.state.shutdown.clone();
+    let cache = handler.state.cache.clone();
 
     let mut client = Client::builder(&token, intents)
         .event_handler(handler)
@@ -278,28 +216,21 @@ async fn main() {
         .await
         .expect("Error creating client");
 
-    // Start periodic cleanup task (runs every hour for all guilds)
+    // Start periodic cleanup task (runs every hour for the shared cache)
     tokio::spawn(async move {
         let mut interval = tokio::time::interval(Duration::from_secs(3600));
         loop {
             interval.tick().await;
-            // Clean up old files for all guilds
-            let guilds: Vec<u64> = {
-                let active = active_files.lock().await;
-                active.keys().copied().collect()
-            };
-            for guild_id in guilds {
-                cleanup_guild_temp_files(guild_id, &active_files).await;
-            }
+            // Drop orphaned files and enforce the cache quota
+            cache.sweep().await;
         }
     });
 
     // Initial cleanup on startup
     cleanup_all_temp_files().await;
 
-    // Create shutdown handler
-    let shutdown = ShutdownHandler::new(queues);
-    let shutdown_trigger = shutdown.is_shutting_down.clone();
+    // Shutdown handler shared with `!shutdown`
+    let shutdown_trigger = shutdown.flag();
 
     // Spawn shutdown handler
     tokio::spawn(async move {
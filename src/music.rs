@@ -1,64 +1,173 @@
-use songbird::input::Input;
-use std::path::PathBuf;
-use tokio::task::spawn_blocking;
+use songbird::input::{
+    codecs::{CODEC_REGISTRY, PROBE},
+    AuxMetadata, ChildContainer, Input,
+};
+use std::{
+    path::{Path, PathBuf},
+    process::Stdio,
+    time::Duration,
+};
+use symphonia::core::{codecs::DecoderOptions, io::MediaSourceStream, probe::Hint};
+use tokio::process::Command;
 
-use crate::cleanup::get_guild_temp_dir;
+use crate::cache::DownloadCache;
+use crate::sources::{self, Capabilities, Playlist, PlaylistEntry, SourceError, SourceInfo};
 
-/// Create an audio source from YouTube (URL or search query)
-/// Returns the Input source and the path to the downloaded file (if any)
+/// An audio source resolved by one of the `SourceProvider`s, ready to be played
+pub struct ResolvedSource {
+    pub input: Input,
+    /// Key of the cached file this source holds a reference on, `None` when streaming.
+    /// The reference must be given back with `DownloadCache::release`.
+    pub cache_key: Option<String>,
+    /// File the input plays, `None` when streaming
+    pub path: Option<PathBuf>,
+    /// Title, uploader, duration etc. reported by the provider
+    pub metadata: AuxMetadata,
+    pub capabilities: Capabilities,
+}
+
+/// Look up title, duration etc. for a query without downloading anything
+pub async fn resolve_metadata(query: &str) -> Result<SourceInfo, SourceError> {
+    let (provider, query) = sources::select(query);
+    provider
+        .lookup(query)
+        .await
+        .map_err(|e| format!("{}: {}", provider.name(), e).into())
+}
+
+/// List the first `limit` tracks if the query is a playlist link its provider
+/// can expand, `None` for a single track
+pub async fn expand_playlist(query: &str, limit: usize) -> Result<Option<Playlist>, SourceError> {
+    let (provider, query) = sources::select(query);
+    provider
+        .playlist(query, limit)
+        .await
+        .map_err(|e| format!("{}: {}", provider.name(), e).into())
+}
+
+/// Top `limit` search results from the provider the query names by prefix,
+/// YouTube by default
+pub async fn search(query: &str, limit: usize) -> Result<Vec<PlaylistEntry>, SourceError> {
+    let (provider, query) = sources::select(query);
+    provider
+        .search(query, limit)
+        .await
+        .map_err(|e| format!("{}: {}", provider.name(), e).into())
+}
+
+/// Create an audio source from the provider the query names by prefix or URL,
+/// searching YouTube for anything else
 pub async fn create_source(
-    guild_id: &u64,
+    cache: &DownloadCache,
     query: &str,
-) -> Result<(Input, Option<PathBuf>), Box<dyn std::error::Error + Send + Sync>> {
-    // Ensure guild temp directory exists
-    let temp_dir = get_guild_temp_dir(*guild_id);
-    if !temp_dir.exists() {
-        spawn_blocking(move || std::fs::create_dir_all(&temp_dir))
-            .await
-            .ok();
-    }
+) -> Result<ResolvedSource, SourceError> {
+    let (provider, query) = sources::select(query);
+    provider
+        .create(cache, query)
+        .await
+        .map_err(|e| format!("{}: {}", provider.name(), e).into())
+}
 
-    // If it's a URL, use it directly. Otherwise, search YouTube
-    let search_query = if query.starts_with("http") {
-        query.to_string()
-    } else {
-        format!("ytsearch1:{}", query)
-    };
+/// Length of an audio file according to ffprobe
+pub async fn probe_duration(path: &Path) -> Option<Duration> {
+    let output = Command::new("ffprobe")
+        .args(["-v", "error", "-show_entries", "format=duration"])
+        .args(["-of", "default=noprint_wrappers=1:nokey=1"])
+        .arg(path)
+        .output()
+        .await
+        .ok()?;
 
-    // Configure YoutubeDl to use guild-specific temp directory
-    use songbird::input::YoutubeDl;
-    let source = YoutubeDl::new(reqwest::Client::new(), search_query)
-        .cache_to(Some(temp_dir.clone()))
+    let secs: f64 = String::from_utf8_lossy(&output.stdout)
+        .trim()
+        .parse()
+        .ok()?;
+    (secs.is_finite() && secs > 0.0).then(|| Duration::from_secs_f64(secs))
+}
+
+/// Measure the integrated loudness of a file in LUFS with ffmpeg's EBU R128 filter
+pub async fn measure_loudness(path: &Path) -> Result<f64, SourceError> {
+    let output = Command::new("ffmpeg")
+        .args(["-nostdin", "-hide_banner", "-nostats", "-i"])
+        .arg(path)
+        .args(["-af", "ebur128=framelog=verbose", "-f", "null", "-"])
+        .output()
         .await?;
 
-    // Try to detect the downloaded file path (if any)
-    let downloaded_file = spawn_blocking(move || {
-        // After yt-dlp downloads, check for new files
-        if let Ok(entries) = std::fs::read_dir(&temp_dir) {
-            let files: Vec<PathBuf> = entries.flatten().map(|e| e.path()).collect();
-            if !files.is_empty() {
-                // Return the most recently modified file
-                let mut latest = None;
-                let mut latest_time = std::time::SystemTime::UNIX_EPOCH;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr)
+            .trim()
+            .to_string()
+            .into());
+    }
+
+    // The summary at the end has a line like `    I:         -14.2 LUFS`
+    let log = String::from_utf8_lossy(&output.stderr);
+    log.lines()
+        .rev()
+        .find_map(|line| {
+            line.trim()
+                .strip_prefix("I:")?
+                .trim()
+                .strip_suffix("LUFS")?
+                .trim()
+                .parse()
+                .ok()
+        })
+        .ok_or_else(|| "ffmpeg did not report the integrated loudness".into())
+}
 
-                for file in files {
-                    if let Ok(meta) = std::fs::metadata(&file) {
-                        if let Ok(modified) = meta.modified() {
-                            if modified > latest_time {
-                                latest_time = modified;
-                                latest = Some(file);
-                            }
-                        }
-                    }
-                }
-                return latest;
-            }
+/// Check that songbird can decode a downloaded file by decoding its first
+/// packet. A file it can't read is deleted, it would only fail every playback.
+pub async fn ensure_decodable(path: &Path) -> Result<(), SourceError> {
+    let file = path.to_path_buf();
+    let decoded = tokio::task::spawn_blocking(move || -> Result<(), SourceError> {
+        let mut hint = Hint::new();
+        if let Some(ext) = file.extension().and_then(|ext| ext.to_str()) {
+            hint.with_extension(ext);
         }
-        None
+        let stream =
+            MediaSourceStream::new(Box::new(std::fs::File::open(&file)?), Default::default());
+        let mut probed = PROBE.format(&hint, stream, &Default::default(), &Default::default())?;
+        let track = probed.format.default_track().ok_or("no audio track")?;
+        let mut decoder = CODEC_REGISTRY.make(&track.codec_params, &DecoderOptions::default())?;
+        decoder.decode(&probed.format.next_packet()?)?;
+        Ok(())
     })
-    .await
-    .ok()
-    .flatten();
+    .await?;
+
+    if let Err(e) = decoded {
+        let _ = tokio::fs::remove_file(path).await;
+        return Err(format!("Can't decode the downloaded file: {}", e).into());
+    }
+    Ok(())
+}
+
+/// Decode a downloaded file through ffmpeg with an audio filter graph,
+/// starting `start` into the file. ffmpeg writes WAV to stdout, which
+/// songbird decodes as it streams in.
+pub fn filtered_input(path: &Path, filter: &str, start: Duration) -> std::io::Result<Input> {
+    let child = std::process::Command::new("ffmpeg")
+        .args(["-nostdin", "-loglevel", "error", "-ss"])
+        .arg(format!("{:.3}", start.as_secs_f64()))
+        .arg("-i")
+        .arg(path)
+        .args(["-af", filter])
+        .args([
+            "-f",
+            "wav",
+            "-acodec",
+            "pcm_s16le",
+            "-ac",
+            "2",
+            "-ar",
+            "48000",
+            "pipe:1",
+        ])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()?;
 
-    Ok((source.into(), downloaded_file))
+    Ok(ChildContainer::from(child).into())
 }
@@ -0,0 +1,273 @@
+use serenity::{
+    builder::{
+        CreateActionRow, CreateButton, CreateEmbed, CreateEmbedAuthor, CreateMessage, EditMessage,
+    },
+    http::Http,
+    model::{
+        application::ButtonStyle,
+        id::{ChannelId, MessageId},
+    },
+};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+use crate::queue::{format_optional_duration, LoopMode, Track};
+
+/// Prefix of the custom ids of the control panel buttons
+pub const BUTTON_PREFIX: &str = "np:";
+
+/// Embed colour while a track is playing
+const PLAYING_COLOUR: u32 = 0x1DB954;
+
+/// Embed colour of announcements for tracks that have finished
+const FINISHED_COLOUR: u32 = 0x4F545C;
+
+/// Player state shown on the control panel
+#[derive(Clone, Copy, Debug)]
+pub struct PanelStatus {
+    pub paused: bool,
+    pub loop_mode: LoopMode,
+    /// Volume in percent
+    pub volume: u32,
+    /// Playback speed factor, durations are shown as they play at it
+    pub speed: f64,
+}
+
+/// What happens to the previous now-playing message when the next track
+/// starts or playback stops. Read from `NOW_PLAYING_CLEANUP`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Cleanup {
+    /// Leave it as it is, only the buttons are disabled
+    Keep,
+    /// Turn it into a compact "played" embed
+    Edit,
+    /// Delete it so only the current track is shown
+    Delete,
+}
+
+impl Cleanup {
+    pub fn from_env() -> Self {
+        match std::env::var("NOW_PLAYING_CLEANUP").as_deref() {
+            Ok("keep") => Cleanup::Keep,
+            Ok("edit") => Cleanup::Edit,
+            _ => Cleanup::Delete,
+        }
+    }
+}
+
+/// The announcement message for the track that is playing
+struct Posted {
+    channel_id: ChannelId,
+    message_id: MessageId,
+    track: Track,
+}
+
+/// Posts a now-playing embed with a control panel to the guild's session text
+/// channel every time a track starts, and tidies up the previous one.
+#[derive(Clone)]
+pub struct Announcer {
+    http: Arc<Http>,
+    cleanup: Cleanup,
+    /// Text channel the voice session was started from
+    channel: Arc<Mutex<Option<ChannelId>>>,
+    last: Arc<Mutex<Option<Posted>>>,
+}
+
+impl Announcer {
+    pub fn new(http: Arc<Http>) -> Self {
+        Self {
+            http,
+            cleanup: Cleanup::from_env(),
+            channel: Arc::new(Mutex::new(None)),
+            last: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Remember the text channel a voice session was started from
+    pub async fn set_channel(&self, channel_id: ChannelId) {
+        *self.channel.lock().await = Some(channel_id);
+    }
+
+    /// Post the now-playing embed for a track that just started. Falls back to
+    /// the channel the track was requested from if no session channel is known.
+    pub async fn announce(&self, track: &Track, status: PanelStatus) {
+        let channel_id = self.channel.lock().await.unwrap_or(track.channel_id);
+
+        let mut last = self.last.lock().await;
+        if let Some(previous) = last.take() {
+            self.tidy(previous).await;
+        }
+
+        let message = CreateMessage::new()
+            .embed(playing_embed(track, status))
+            .components(panel(false));
+        match channel_id.send_message(&self.http, message).await {
+            Ok(message) => {
+                *last = Some(Posted {
+                    channel_id,
+                    message_id: message.id,
+                    track: track.clone(),
+                });
+            }
+            Err(e) => println!("⚠️ Failed to post now playing message: {}", e),
+        }
+    }
+
+    /// Update the embed of the current announcement in place
+    pub async fn refresh(&self, status: PanelStatus) {
+        let last = self.last.lock().await;
+        let Some(posted) = last.as_ref() else {
+            return;
+        };
+
+        let edit = EditMessage::new()
+            .embed(playing_embed(&posted.track, status))
+            .components(panel(false));
+        if let Err(e) = posted
+            .channel_id
+            .edit_message(&self.http, posted.message_id, edit)
+            .await
+        {
+            println!("⚠️ Failed to update now playing message: {}", e);
+        }
+    }
+
+    /// Whether a message is the announcement of the track that is playing
+    pub async fn is_current(&self, message_id: MessageId) -> bool {
+        self.last
+            .lock()
+            .await
+            .as_ref()
+            .is_some_and(|posted| posted.message_id == message_id)
+    }
+
+    /// Tidy up the last announcement once nothing is playing anymore
+    pub async fn finish(&self) {
+        if let Some(previous) = self.last.lock().await.take() {
+            self.tidy(previous).await;
+        }
+    }
+
+    async fn tidy(&self, posted: Posted) {
+        let result = match self.cleanup {
+            Cleanup::Keep => posted
+                .channel_id
+                .edit_message(
+                    &self.http,
+                    posted.message_id,
+                    EditMessage::new().components(panel(true)),
+                )
+                .await
+                .map(|_| ()),
+            Cleanup::Edit => posted
+                .channel_id
+                .edit_message(
+                    &self.http,
+                    posted.message_id,
+                    EditMessage::new()
+                        .embed(finished_embed(&posted.track))
+                        .components(panel(true)),
+                )
+                .await
+                .map(|_| ()),
+            Cleanup::Delete => {
+                posted
+                    .channel_id
+                    .delete_message(&self.http, posted.message_id)
+                    .await
+            }
+        };
+
+        // Someone may have deleted it already, nothing else to do then
+        if let Err(e) = result {
+            println!("⚠️ Failed to tidy now playing message: {}", e);
+        }
+    }
+}
+
+/// Rich embed for the track that is playing
+fn playing_embed(track: &Track, status: PanelStatus) -> CreateEmbed {
+    let heading = if status.paused {
+        "⏸️ Paused"
+    } else {
+        "🎵 Now playing"
+    };
+
+    let mut embed = CreateEmbed::new()
+        .author(CreateEmbedAuthor::new(heading))
+        .title(track.display_title())
+        .colour(PLAYING_COLOUR)
+        .field("Duration", length(track, status.speed), true)
+        .field("Requested by", format!("<@{}>", track.requester), true);
+
+    if let Some(url) = &track.source_url {
+        embed = embed.url(url);
+    }
+    if let Some(uploader) = &track.uploader {
+        embed = embed.field("Uploader", uploader, true);
+    }
+    embed = embed.field("Loop", status.loop_mode.label(), true).field(
+        "Volume",
+        format!("{}%", status.volume),
+        true,
+    );
+    if status.speed != 1.0 {
+        embed = embed.field("Speed", format!("{}×", status.speed), true);
+    }
+    if let Some(thumbnail) = &track.thumbnail {
+        embed = embed.thumbnail(thumbnail);
+    }
+    embed
+}
+
+/// Track length as it plays at `speed`, or a live marker for streams
+fn length(track: &Track, speed: f64) -> String {
+    if track.capabilities.live {
+        "🔴 Live".to_string()
+    } else {
+        format_optional_duration(track.playback_duration(speed))
+    }
+}
+
+/// Compact embed an announcement is turned into once its track is over
+fn finished_embed(track: &Track) -> CreateEmbed {
+    let mut embed = CreateEmbed::new()
+        .author(CreateEmbedAuthor::new("Played"))
+        .title(track.display_title())
+        .colour(FINISHED_COLOUR)
+        .description(format!(
+            "`[{}]` • requested by <@{}>",
+            length(track, 1.0),
+            track.requester
+        ));
+
+    if let Some(url) = &track.source_url {
+        embed = embed.url(url);
+    }
+    embed
+}
+
+/// Control panel buttons. Their custom ids are `BUTTON_PREFIX` followed by
+/// the action, see `controls::handle`.
+fn panel(disabled: bool) -> Vec<CreateActionRow> {
+    let button = |action: &str, label: &str, style: ButtonStyle| {
+        CreateButton::new(format!("{}{}", BUTTON_PREFIX, action))
+            .label(label)
+            .style(style)
+            .disabled(disabled)
+    };
+
+    vec![
+        CreateActionRow::Buttons(vec![
+            button("pause", "⏯️ Pause/Resume", ButtonStyle::Primary),
+            button("skip", "⏭️ Skip", ButtonStyle::Secondary),
+            button("stop", "⏹️ Stop", ButtonStyle::Danger),
+            button("loop", "🔁 Loop", ButtonStyle::Secondary),
+            button("shuffle", "🔀 Shuffle", ButtonStyle::Secondary),
+        ]),
+        CreateActionRow::Buttons(vec![
+            button("volume_down", "🔉 Volume -", ButtonStyle::Secondary),
+            button("volume_up", "🔊 Volume +", ButtonStyle::Secondary),
+        ]),
+    ]
+}
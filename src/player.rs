@@ -0,0 +1,1022 @@
+use serenity::{
+    http::Http,
+    model::id::{ChannelId, GuildId, MessageId},
+};
+use songbird::{
+    input::{File, Input},
+    tracks::{ControlError, PlayMode, TrackHandle},
+    Call, Songbird, TrackEvent,
+};
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tokio::{sync::Mutex, task::JoinHandle};
+
+use crate::cache::DownloadCache;
+use crate::events::{CrossfadeNotifier, TrackEndNotifier, TrackErrorNotifier};
+use crate::filters::AudioFilters;
+use crate::music::{create_source, filtered_input, measure_loudness};
+use crate::now_playing::{Announcer, PanelStatus};
+use crate::prefetch::Prefetcher;
+use crate::queue::{format_duration, LoopMode, LoopModes, Queue, Track};
+use crate::settings::{GuildSettings, Settings};
+use crate::state::BotState;
+
+pub type PlayerMap = Arc<Mutex<HashMap<u64, Player>>>;
+
+/// How often a track is tried before it is skipped
+const MAX_ATTEMPTS: u32 = 3;
+
+/// Delay before the first retry, doubled for every further attempt
+const RETRY_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Default for `IDLE_TIMEOUT_SECS`
+const DEFAULT_IDLE_TIMEOUT_SECS: u64 = 300;
+
+/// Most a quiet track is boosted by loudness normalization, in dB
+const MAX_NORMALIZATION_BOOST_DB: f64 = 12.0;
+
+/// Interval between volume changes while fading
+const FADE_STEP: Duration = Duration::from_millis(100);
+
+/// Why the player is waiting to leave the voice channel
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Inactivity {
+    /// Nothing is playing and the queue is empty
+    Idle,
+    /// No listeners other than bots are left in the voice channel
+    Alone,
+}
+
+/// Pending leave of an inactive player
+struct LeaveTimer {
+    reason: Inactivity,
+    handle: JoinHandle<()>,
+}
+
+/// The track the player is currently playing
+struct NowPlaying {
+    handle: TrackHandle,
+    /// Identifies this playback, a restarted track gets a new one
+    play_id: u64,
+    source: PlaybackSource,
+}
+
+/// Which way `Player::ramp_volume` fades a track
+#[derive(Clone, Copy)]
+enum Fade {
+    /// The next track fading in, following its gain as it changes
+    In { play_id: u64, gain: f32 },
+    /// The previous track fading out
+    Out { gain: f32 },
+}
+
+/// What a playback was started from
+#[derive(Clone)]
+struct PlaybackSource {
+    track_id: u64,
+    /// Cached file, each playback of it holds a reference in `cache_refs`
+    cache_key: Option<String>,
+    /// Downloaded file, needed to restart the track with other filters
+    path: Option<PathBuf>,
+    /// Position in the file the input started at
+    offset: Duration,
+    /// Whether the input is decoded through the ffmpeg filter graph, which
+    /// can't seek and has to be restarted instead
+    filtered: bool,
+    /// Speed factor the input plays at, converting played time to track time
+    speed: f64,
+    /// Loudness normalization gain, applied on top of the volume
+    gain: f32,
+    /// Length of the track, used to time the crossfade into the next one
+    duration: Option<Duration>,
+}
+
+/// Per-guild playback controller.
+///
+/// Owns the handle of the playing track and is the only place that starts
+/// tracks, so `!play`, `!skip`, `!stop` and the track end handler all drive
+/// the bot's queue the same way. The queue entry at index 0 is the one playing.
+#[derive(Clone)]
+pub struct Player {
+    guild_id: GuildId,
+    manager: Arc<Songbird>,
+    http: Arc<Http>,
+    queue: Queue,
+    loop_modes: LoopModes,
+    cache: DownloadCache,
+    prefetcher: Prefetcher,
+    announcer: Announcer,
+    current: Arc<Mutex<Option<NowPlaying>>>,
+    /// Set by `skip` so the next track end advances even in track loop mode
+    skip_requested: Arc<AtomicBool>,
+    /// Stored guild settings such as the volume
+    settings: Settings,
+    /// Audio effects applied to every track
+    filters: Arc<Mutex<AudioFilters>>,
+    /// Failed attempts per queue entry
+    failures: Arc<Mutex<HashMap<u64, u32>>>,
+    /// Cache reference held by each playback by play id, until it is released
+    cache_refs: Arc<Mutex<HashMap<u64, String>>>,
+    /// Held while starting a track so two callers never start one each
+    play_lock: Arc<Mutex<()>>,
+    /// How long to stay connected while inactive, from `IDLE_TIMEOUT_SECS`
+    idle_timeout: Duration,
+    /// Pending leave, if the player is inactive
+    leave_timer: Arc<Mutex<Option<LeaveTimer>>>,
+    /// Set when playback was paused because everyone left the channel
+    auto_paused: Arc<AtomicBool>,
+    /// Set during a crossfade so the next track starts by fading in
+    fade_in_next: Arc<AtomicBool>,
+    /// Previous track still fading out during a crossfade
+    fading_out: Arc<Mutex<Option<TrackHandle>>>,
+}
+
+impl Player {
+    /// Player for a guild, sharing the bot-wide parts of `state`
+    pub fn new(
+        guild_id: GuildId,
+        manager: Arc<Songbird>,
+        http: Arc<Http>,
+        queue: Queue,
+        state: &BotState,
+    ) -> Self {
+        Self {
+            guild_id,
+            manager,
+            announcer: Announcer::new(http.clone()),
+            http,
+            queue,
+            loop_modes: state.loop_modes.clone(),
+            cache: state.cache.clone(),
+            prefetcher: state.prefetcher.clone(),
+            current: Arc::new(Mutex::new(None)),
+            skip_requested: Arc::new(AtomicBool::new(false)),
+            settings: state.settings.clone(),
+            filters: Arc::new(Mutex::new(AudioFilters::default())),
+            failures: Arc::new(Mutex::new(HashMap::new())),
+            cache_refs: Arc::new(Mutex::new(HashMap::new())),
+            play_lock: Arc::new(Mutex::new(())),
+            idle_timeout: Duration::from_secs(
+                std::env::var("IDLE_TIMEOUT_SECS")
+                    .ok()
+                    .and_then(|value| value.parse().ok())
+                    .unwrap_or(DEFAULT_IDLE_TIMEOUT_SECS),
+            ),
+            leave_timer: Arc::new(Mutex::new(None)),
+            auto_paused: Arc::new(AtomicBool::new(false)),
+            fade_in_next: Arc::new(AtomicBool::new(false)),
+            fading_out: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Remember the text channel the voice session was started from, where
+    /// now-playing messages are posted
+    pub async fn set_text_channel(&self, channel_id: ChannelId) {
+        self.announcer.set_channel(channel_id).await;
+    }
+
+    /// Whether a track is loaded (playing or paused)
+    pub async fn is_active(&self) -> bool {
+        self.current.lock().await.is_some()
+    }
+
+    /// Start the entry at the head of the queue if nothing is playing.
+    ///
+    /// Entries that fail are retried with backoff and then dropped, telling the
+    /// channel they were requested from why. Returns the started track, or
+    /// `None` if something was already playing or nothing could be played.
+    pub async fn play_next(&self) -> Option<Track> {
+        let _guard = self.play_lock.lock().await;
+
+        loop {
+            // Not connected (anymore), keep the queue for the next join
+            if self.is_active().await || self.manager.get(self.guild_id).is_none() {
+                return None;
+            }
+
+            let head = match self.queue.lock().await.first().cloned() {
+                Some(head) => head,
+                None => {
+                    self.announcer.finish().await;
+                    self.schedule_leave(Inactivity::Idle).await;
+                    return None;
+                }
+            };
+            match self.start(&head).await {
+                Ok(track) => {
+                    self.failures.lock().await.remove(&track.id);
+                    self.cancel_leave(Inactivity::Idle).await;
+                    let status = self.panel_status().await;
+                    self.announcer.announce(&track, status).await;
+                    return Some(track);
+                }
+                Err(reason) => {
+                    // Removed or replaced (e.g. by !stop) while it was loading
+                    if self.queue.lock().await.first().map(|t| t.id) != Some(head.id) {
+                        continue;
+                    }
+                    self.record_failure(&head, &reason).await;
+                }
+            }
+        }
+    }
+
+    /// Start playing a queue entry. Errors are reported as text.
+    async fn start(&self, head: &Track) -> Result<Track, String> {
+        let call_lock = match self.manager.get(self.guild_id) {
+            Some(call_lock) => call_lock,
+            None => return Err("I'm not in a voice channel!".to_string()),
+        };
+
+        // Use the prefetched download if it finished, otherwise fetch it now
+        let resolved = match self.prefetcher.take(self.guild_id.get(), head.id).await {
+            Some(resolved) => resolved,
+            None => create_source(&self.cache, &head.query)
+                .await
+                .map_err(|e| e.to_string())?,
+        };
+
+        // Fill in the queue entry with the resolved metadata, unless it was
+        // removed while downloading
+        let mut track = head.clone();
+        track.apply_metadata(&resolved.metadata, resolved.capabilities);
+        {
+            let mut queue = self.queue.lock().await;
+            match queue.first_mut() {
+                Some(entry) if entry.id == track.id => *entry = track.clone(),
+                _ => {
+                    drop(queue);
+                    if let Some(ref key) = resolved.cache_key {
+                        self.cache.release(key).await;
+                    }
+                    return Err("Removed from the queue".to_string());
+                }
+            }
+        }
+
+        // Decode through ffmpeg if filters are on, which needs the downloaded file
+        let (filter, speed) = {
+            let filters = self.filters.lock().await;
+            (filters.ffmpeg_filter(), filters.effective_speed())
+        };
+        let (input, filtered) = match (filter, &resolved.path) {
+            (Some(filter), Some(path)) => match filtered_input(path, &filter, Duration::ZERO) {
+                Ok(input) => (input, true),
+                Err(e) => {
+                    println!("⚠️ Guild {}: Playing without filters: {}", self.guild_id, e);
+                    (resolved.input, false)
+                }
+            },
+            _ => (resolved.input, false),
+        };
+
+        // Prefetched tracks were measured while downloading, others are
+        // measured here so the track starts at its final volume
+        let gain = self.normalization_gain(resolved.cache_key.as_deref()).await;
+        let source = PlaybackSource {
+            track_id: track.id,
+            cache_key: resolved.cache_key,
+            path: resolved.path,
+            offset: Duration::ZERO,
+            filtered,
+            speed: if filtered { speed } else { 1.0 },
+            gain,
+            duration: track.duration,
+        };
+        let now = self.play_input(&call_lock, input, source).await;
+        *self.current.lock().await = Some(now);
+
+        // Download the following entries while this one plays
+        self.prefetcher.prefetch(self.guild_id.get(), &self.queue).await;
+
+        Ok(track)
+    }
+
+    /// Play an input on the call with the guild's volume and register the
+    /// track end and error handlers
+    async fn play_input(
+        &self,
+        call_lock: &Mutex<Call>,
+        input: Input,
+        source: PlaybackSource,
+    ) -> NowPlaying {
+        static NEXT_PLAY_ID: AtomicU64 = AtomicU64::new(1);
+        let play_id = NEXT_PLAY_ID.fetch_add(1, Ordering::Relaxed);
+        if let Some(ref key) = source.cache_key {
+            self.cache_refs.lock().await.insert(play_id, key.clone());
+        }
+
+        let handle = call_lock.lock().await.play_input(input);
+        let settings = self.settings.get(self.guild_id.get()).await;
+        let level = settings.volume as f32 / 100.0 * source.gain;
+        let crossfade = Duration::from_secs(settings.crossfade_secs as u64);
+        if self.fade_in_next.swap(false, Ordering::SeqCst) && !crossfade.is_zero() {
+            let _ = handle.set_volume(0.0);
+            let player = self.clone();
+            let fading = handle.clone();
+            let fade = Fade::In {
+                play_id,
+                gain: source.gain,
+            };
+            tokio::spawn(async move { player.ramp_volume(&fading, fade, crossfade).await });
+        } else {
+            let _ = handle.set_volume(level);
+        }
+
+        // Start the next track early so the two overlap
+        if let Some(duration) = source.duration.filter(|_| !crossfade.is_zero()) {
+            let remaining = duration.saturating_sub(source.offset).div_f64(source.speed);
+            if remaining > crossfade {
+                let _ = handle.add_event(
+                    songbird::Event::Delayed(remaining - crossfade),
+                    CrossfadeNotifier {
+                        player: self.clone(),
+                        play_id,
+                        track_id: source.track_id,
+                    },
+                );
+            }
+        }
+
+        // Add event handlers for when the track ends or fails
+        let _ = handle.add_event(
+            songbird::Event::Track(TrackEvent::End),
+            TrackEndNotifier {
+                player: self.clone(),
+                play_id,
+                track_id: source.track_id,
+            },
+        );
+        let _ = handle.add_event(
+            songbird::Event::Track(TrackEvent::Error),
+            TrackErrorNotifier {
+                player: self.clone(),
+                play_id,
+                track_id: source.track_id,
+            },
+        );
+
+        NowPlaying {
+            handle,
+            play_id,
+            source,
+        }
+    }
+
+    /// Restart the current track at `position` of its file, decoded with the
+    /// current filters. Returns the position it restarted at.
+    async fn restart_at(&self, position: Duration) -> Result<Duration, String> {
+        let call_lock = self
+            .manager
+            .get(self.guild_id)
+            .ok_or_else(|| "I'm not in a voice channel!".to_string())?;
+
+        let mut current = self.current.lock().await;
+        let Some(now) = current.as_ref() else {
+            return Err("Nothing is playing!".to_string());
+        };
+        let Some(path) = now.source.path.clone() else {
+            return Err(
+                "This song is streamed, filters apply from the next downloaded song.".to_string(),
+            );
+        };
+
+        let (filter, speed) = {
+            let filters = self.filters.lock().await;
+            (filters.ffmpeg_filter(), filters.effective_speed())
+        };
+        let (input, filtered, offset, speed) = match filter {
+            Some(filter) => (
+                filtered_input(&path, &filter, position).map_err(|e| e.to_string())?,
+                true,
+                position,
+                speed,
+            ),
+            None => (File::new(path).into(), false, Duration::ZERO, 1.0),
+        };
+
+        // The new playback holds its own reference, the old one gives its
+        // back when it ends
+        if let Some(ref key) = now.source.cache_key {
+            self.cache.acquire(key).await;
+        }
+        let paused = matches!(
+            now.handle.get_info().await.map(|info| info.playing),
+            Ok(PlayMode::Pause)
+        );
+
+        let source = PlaybackSource {
+            offset,
+            filtered,
+            speed,
+            ..now.source.clone()
+        };
+        let restarted = self.play_input(&call_lock, input, source).await;
+        if !filtered {
+            let _ = restarted.handle.seek(position);
+        }
+        if paused {
+            let _ = restarted.handle.pause();
+        }
+
+        // Replace before stopping so the old end event is ignored
+        if let Some(old) = current.replace(restarted) {
+            let _ = old.handle.stop();
+        }
+        Ok(position)
+    }
+
+    /// Count a failed attempt for a queue entry. Waits out the backoff if it
+    /// should be retried, otherwise drops it and tells the requester.
+    async fn record_failure(&self, track: &Track, reason: &str) {
+        let attempts = {
+            let mut failures = self.failures.lock().await;
+            let attempts = failures.entry(track.id).or_insert(0);
+            *attempts += 1;
+            *attempts
+        };
+
+        if attempts < MAX_ATTEMPTS {
+            println!(
+                "⚠️ Guild {}: Attempt {} for \"{}\" failed, retrying: {}",
+                self.guild_id,
+                attempts,
+                track.display_title(),
+                reason
+            );
+            tokio::time::sleep(RETRY_BACKOFF * 2u32.pow(attempts - 1)).await;
+            return;
+        }
+
+        self.failures.lock().await.remove(&track.id);
+        self.queue.lock().await.retain(|t| t.id != track.id);
+
+        let _ = track
+            .channel_id
+            .say(
+                &self.http,
+                format!(
+                    "⚠️ <@{}> Couldn't play **{}** after {} attempts, skipping it: {}",
+                    track.requester,
+                    track.display_title(),
+                    MAX_ATTEMPTS,
+                    reason
+                ),
+            )
+            .await;
+    }
+
+    /// Called by the track end handler. Advances the queue according to the
+    /// loop mode and starts the next entry.
+    pub async fn on_track_end(&self, play_id: u64, track_id: u64) {
+        self.release_cache_ref(play_id).await;
+
+        {
+            let mut current = self.current.lock().await;
+            // Ignore tracks that were replaced, restarted or stopped on purpose
+            if current.as_ref().map(|c| c.play_id) != Some(play_id) {
+                return;
+            }
+            *current = None;
+        }
+
+        let skipped = self.skip_requested.swap(false, Ordering::SeqCst);
+        self.advance(track_id, skipped).await;
+
+        // Play next song if available
+        self.play_next().await;
+    }
+
+    /// Called by the crossfade handler shortly before a track ends. Starts the
+    /// next entry fading in while this one fades out. Returns how much longer
+    /// to wait if the track isn't that far anymore, e.g. after seeking back.
+    pub async fn on_crossfade(&self, play_id: u64, track_id: u64) -> Option<Duration> {
+        let settings = self.settings.get(self.guild_id.get()).await;
+        let crossfade = Duration::from_secs(settings.crossfade_secs as u64);
+        if crossfade.is_zero() {
+            return None;
+        }
+
+        // Nothing would follow the last entry, let it end normally
+        let loop_mode = self.loop_mode().await;
+        if loop_mode == LoopMode::Off && self.queue.lock().await.len() <= 1 {
+            return None;
+        }
+
+        let old = {
+            let mut current = self.current.lock().await;
+            let now = current.as_ref().filter(|now| now.play_id == play_id)?;
+            let position = now.handle.get_info().await.ok()?.position;
+            let position = now.source.offset + position.mul_f64(now.source.speed);
+            let remaining = now
+                .source
+                .duration?
+                .saturating_sub(position)
+                .div_f64(now.source.speed);
+            if remaining > crossfade + FADE_STEP {
+                return Some(remaining - crossfade);
+            }
+            // Its end event is ignored from now on
+            current.take()?
+        };
+
+        self.fade_out(old.handle, old.source.gain, crossfade).await;
+        self.advance(track_id, false).await;
+
+        self.fade_in_next.store(true, Ordering::SeqCst);
+        self.play_next().await;
+        self.fade_in_next.store(false, Ordering::SeqCst);
+        None
+    }
+
+    /// Fade a track out and stop it, cutting short any earlier fade
+    async fn fade_out(&self, handle: TrackHandle, gain: f32, length: Duration) {
+        if let Some(previous) = self.fading_out.lock().await.replace(handle.clone()) {
+            let _ = previous.stop();
+        }
+
+        let player = self.clone();
+        tokio::spawn(async move {
+            player
+                .ramp_volume(&handle, Fade::Out { gain }, length)
+                .await;
+            let _ = handle.stop();
+            let mut fading = player.fading_out.lock().await;
+            if fading.as_ref().map(|h| h.uuid()) == Some(handle.uuid()) {
+                *fading = None;
+            }
+        });
+    }
+
+    /// Step a track's volume between silence and the guild's volume over
+    /// `length`. The volume is read again on every step, so `!volume` and
+    /// normalization changes during a fade aren't lost.
+    async fn ramp_volume(&self, handle: &TrackHandle, fade: Fade, length: Duration) {
+        let steps = (length.as_millis() / FADE_STEP.as_millis()).max(1) as u32;
+        for step in 1..=steps {
+            tokio::time::sleep(FADE_STEP).await;
+            let progress = step as f32 / steps as f32;
+            let (gain, share) = match fade {
+                Fade::In { play_id, gain } => match &*self.current.lock().await {
+                    Some(now) if now.play_id == play_id => (now.source.gain, progress),
+                    // Replaced, e.g. by the next crossfade, which fades it out
+                    Some(_) => return,
+                    // Not stored as the current track yet
+                    None => (gain, progress),
+                },
+                Fade::Out { gain } => (gain, 1.0 - progress),
+            };
+            let volume = self.settings.get(self.guild_id.get()).await.volume as f32 / 100.0;
+            // Gone once the track has ended
+            if handle.set_volume(volume * gain * share).is_err() {
+                return;
+            }
+        }
+    }
+
+    /// Stop the track fading out, if a crossfade is in progress
+    async fn cut_fade(&self) {
+        if let Some(handle) = self.fading_out.lock().await.take() {
+            let _ = handle.stop();
+        }
+    }
+
+    /// Release a playback's reference on its cached file. Does nothing if it
+    /// was already released, e.g. by `teardown`.
+    async fn release_cache_ref(&self, play_id: u64) {
+        let key = self.cache_refs.lock().await.remove(&play_id);
+        if let Some(key) = key {
+            self.cache.release(&key).await;
+        }
+    }
+
+    /// The guild's loop mode
+    pub async fn loop_mode(&self) -> LoopMode {
+        self.loop_modes
+            .lock()
+            .await
+            .get(&self.guild_id.get())
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Move past a finished queue entry according to the loop mode
+    async fn advance(&self, track_id: u64, skipped: bool) {
+        let loop_mode = self.loop_mode().await;
+
+        // Advance past the finished song according to the loop mode
+        {
+            let mut queue = self.queue.lock().await;
+            if queue.first().map(|t| t.id) == Some(track_id) {
+                match loop_mode {
+                    // Keep it at the head unless skipped, its file is still in the cache
+                    LoopMode::Track if !skipped => {}
+                    LoopMode::Off | LoopMode::Track => {
+                        queue.remove(0);
+                    }
+                    LoopMode::Queue => {
+                        let finished = queue.remove(0);
+                        queue.push(finished);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Called by the track error handler. Retries the entry with backoff,
+    /// then skips to the next one.
+    pub async fn on_track_error(&self, play_id: u64, track_id: u64, reason: String) {
+        self.release_cache_ref(play_id).await;
+
+        let cache_key = {
+            let mut current = self.current.lock().await;
+            if current.as_ref().map(|c| c.play_id) != Some(play_id) {
+                return;
+            }
+            current.take().and_then(|now| now.source.cache_key)
+        };
+        // The cached file may be what's broken, drop it once nobody else uses it
+        if let Some(key) = cache_key {
+            self.cache.invalidate(&key).await;
+        }
+
+        let failed = self
+            .queue
+            .lock()
+            .await
+            .iter()
+            .find(|t| t.id == track_id)
+            .cloned();
+        if let Some(track) = failed {
+            self.record_failure(&track, &reason).await;
+        }
+
+        self.play_next().await;
+    }
+
+    pub async fn pause(&self) -> Result<(), String> {
+        self.cut_fade().await;
+        match &*self.current.lock().await {
+            Some(now) => now.handle.pause().map_err(|e| e.to_string())?,
+            None => return Err("Nothing is playing!".to_string()),
+        }
+        self.refresh_panel().await;
+        Ok(())
+    }
+
+    pub async fn resume(&self) -> Result<(), String> {
+        match &*self.current.lock().await {
+            Some(now) => now.handle.play().map_err(|e| e.to_string())?,
+            None => return Err("Nothing is playing!".to_string()),
+        }
+        self.refresh_panel().await;
+        Ok(())
+    }
+
+    /// Whether the current track is paused
+    pub async fn is_paused(&self) -> bool {
+        let handle = match &*self.current.lock().await {
+            Some(now) => now.handle.clone(),
+            None => return false,
+        };
+        matches!(
+            handle.get_info().await.map(|info| info.playing),
+            Ok(PlayMode::Pause)
+        )
+    }
+
+    /// How far into the current track playback is, in track time
+    pub async fn position(&self) -> Option<Duration> {
+        let (handle, offset, speed) = match &*self.current.lock().await {
+            Some(now) => (now.handle.clone(), now.source.offset, now.source.speed),
+            None => return None,
+        };
+        handle
+            .get_info()
+            .await
+            .ok()
+            .map(|info| offset + info.position.mul_f64(speed))
+    }
+
+    /// Jump to a position in the current track. Returns the new position.
+    pub async fn seek(&self, position: Duration) -> Result<Duration, String> {
+        let (handle, track_id, filtered) = match &*self.current.lock().await {
+            Some(now) => (now.handle.clone(), now.source.track_id, now.source.filtered),
+            None => return Err("Nothing is playing!".to_string()),
+        };
+
+        let head = self
+            .queue
+            .lock()
+            .await
+            .first()
+            .filter(|track| track.id == track_id)
+            .map(|track| (track.duration, track.capabilities));
+        if let Some((_, capabilities)) = head.filter(|(_, c)| !c.seekable) {
+            return Err(if capabilities.live {
+                "Can't seek in a live stream.".to_string()
+            } else {
+                "This source doesn't support seeking.".to_string()
+            });
+        }
+        if let Some(duration) = head.and_then(|(duration, _)| duration) {
+            if position >= duration {
+                return Err(format!(
+                    "That's past the end of the track ({}).",
+                    format_duration(duration)
+                ));
+            }
+        }
+
+        // The ffmpeg filter pipe can't seek, start it again at the position
+        if filtered {
+            return self.restart_at(position).await;
+        }
+
+        handle.seek_async(position).await.map_err(|e| match e {
+            ControlError::Play(_) => "❌ This track can't be seeked.".to_string(),
+            ControlError::Finished => "Nothing is playing!".to_string(),
+            e => format!("❌ Seeking failed: {}", e),
+        })
+    }
+
+    /// Move forward (or back, for negative `offset_secs`) in the current track.
+    /// Returns the new position.
+    pub async fn seek_by(&self, offset_secs: i64) -> Result<Duration, String> {
+        let position = self
+            .position()
+            .await
+            .ok_or_else(|| "Nothing is playing!".to_string())?;
+        let offset = Duration::from_secs(offset_secs.unsigned_abs());
+        let target = if offset_secs >= 0 {
+            position + offset
+        } else {
+            position.saturating_sub(offset)
+        };
+        self.seek(target).await
+    }
+
+    /// Audio effects currently applied
+    pub async fn filters(&self) -> AudioFilters {
+        self.filters.lock().await.clone()
+    }
+
+    /// Change the audio effects. The current track is restarted from its
+    /// position with the new effects. Errors if they can only apply from the
+    /// next track.
+    pub async fn set_filters(&self, filters: AudioFilters) -> Result<(), String> {
+        *self.filters.lock().await = filters;
+
+        let Some(position) = self.position().await else {
+            return Ok(());
+        };
+        let restarted = self.restart_at(position).await.map(|_| ());
+        self.refresh_panel().await;
+        restarted
+    }
+
+    /// Set the guild's volume in percent, clamped to its maximum. Applies to
+    /// the current track and every later one. Returns the new volume.
+    pub async fn set_volume(&self, volume: u32) -> u32 {
+        let settings = self
+            .settings
+            .update(self.guild_id.get(), |settings| {
+                settings.volume = volume.min(settings.max_volume)
+            })
+            .await;
+        self.apply_volume(settings.volume).await;
+        settings.volume
+    }
+
+    /// Change the volume by `delta` percent. Returns the new volume.
+    pub async fn change_volume(&self, delta: i32) -> u32 {
+        let current = self.settings.get(self.guild_id.get()).await.volume;
+        self.set_volume(current.saturating_add_signed(delta)).await
+    }
+
+    /// Set the highest volume members may choose, lowering the current volume
+    /// if it is above it. Returns the new volume.
+    pub async fn set_max_volume(&self, max_volume: u32) -> u32 {
+        let settings = self
+            .settings
+            .update(self.guild_id.get(), |settings| {
+                settings.max_volume = max_volume;
+                settings.volume = settings.volume.min(max_volume);
+            })
+            .await;
+        self.apply_volume(settings.volume).await;
+        settings.volume
+    }
+
+    async fn apply_volume(&self, volume: u32) {
+        if let Some(now) = &*self.current.lock().await {
+            let _ = now
+                .handle
+                .set_volume(volume as f32 / 100.0 * now.source.gain);
+        }
+        self.refresh_panel().await;
+    }
+
+    /// Turn loudness normalization on or off and set its target. Applies to
+    /// the current track and every later one. Returns the new settings.
+    pub async fn set_normalization(&self, normalize: bool, target_lufs: i32) -> GuildSettings {
+        let settings = self
+            .settings
+            .update(self.guild_id.get(), |settings| {
+                settings.normalize = normalize;
+                settings.target_lufs = target_lufs;
+            })
+            .await;
+        self.update_gain().await;
+        settings
+    }
+
+    /// Recompute the normalization gain of the current track, measuring it
+    /// if needed, and apply it unless another track started meanwhile
+    async fn update_gain(&self) {
+        let (play_id, cache_key) = match &*self.current.lock().await {
+            Some(now) => (now.play_id, now.source.cache_key.clone()),
+            None => return,
+        };
+        let gain = self.normalization_gain(cache_key.as_deref()).await;
+        if let Some(now) = &mut *self.current.lock().await {
+            if now.play_id != play_id {
+                return;
+            }
+            now.source.gain = gain;
+        }
+        let volume = self.settings.get(self.guild_id.get()).await.volume;
+        self.apply_volume(volume).await;
+    }
+
+    /// Gain bringing a downloaded track to the guild's loudness target. The
+    /// loudness is measured the first time and kept with the cache entry. 1
+    /// when normalization is off, the track is streamed or can't be measured.
+    async fn normalization_gain(&self, cache_key: Option<&str>) -> f32 {
+        let settings = self.settings.get(self.guild_id.get()).await;
+        let (true, Some(key)) = (settings.normalize, cache_key) else {
+            return 1.0;
+        };
+
+        let measured = self
+            .cache
+            .loudness(key, |path| async move { measure_loudness(&path).await })
+            .await;
+        let loudness = match measured {
+            Ok(loudness) => loudness,
+            Err(e) => {
+                println!(
+                    "⚠️ Guild {}: Couldn't measure loudness: {}",
+                    self.guild_id, e
+                );
+                return 1.0;
+            }
+        };
+
+        let gain_db = (settings.target_lufs as f64 - loudness).min(MAX_NORMALIZATION_BOOST_DB);
+        10f64.powf(gain_db / 20.0) as f32
+    }
+
+    /// Whether a message is the control panel of the track that is playing
+    pub async fn is_panel(&self, message_id: MessageId) -> bool {
+        self.announcer.is_current(message_id).await
+    }
+
+    /// Update the now-playing embed after the player state changed
+    pub async fn refresh_panel(&self) {
+        let status = self.panel_status().await;
+        self.announcer.refresh(status).await;
+    }
+
+    async fn panel_status(&self) -> PanelStatus {
+        PanelStatus {
+            paused: self.is_paused().await,
+            loop_mode: self.loop_mode().await,
+            volume: self.settings.get(self.guild_id.get()).await.volume,
+            speed: self.filters.lock().await.effective_speed(),
+        }
+    }
+
+    /// Stop the current track, the track end handler then plays the next entry
+    pub async fn skip(&self) -> Result<(), String> {
+        match &*self.current.lock().await {
+            Some(now) => {
+                self.skip_requested.store(true, Ordering::SeqCst);
+                now.handle.stop().map_err(|e| e.to_string())
+            }
+            None => Err("Nothing is playing!".to_string()),
+        }
+    }
+
+    /// Clear the queue and stop the current track
+    pub async fn stop(&self) {
+        self.queue.lock().await.clear();
+        self.prefetcher.clear(self.guild_id.get()).await;
+
+        // Forget the track first so its end event doesn't advance the queue
+        if let Some(now) = self.current.lock().await.take() {
+            let _ = now.handle.stop();
+        }
+        self.cut_fade().await;
+        self.announcer.finish().await;
+    }
+
+    /// Start the idle timer if nothing is playing or queued
+    pub async fn check_idle(&self) {
+        if !self.is_active().await && self.queue.lock().await.is_empty() {
+            self.schedule_leave(Inactivity::Idle).await;
+        }
+    }
+
+    /// Called when the number of non-bot members in the bot's voice channel
+    /// changes. Pauses and starts the leave timer once everyone has left, and
+    /// resumes when someone comes back.
+    pub async fn on_listeners_changed(&self, listeners: usize) {
+        if listeners == 0 {
+            if !self.is_paused().await && self.pause().await.is_ok() {
+                self.auto_paused.store(true, Ordering::SeqCst);
+            }
+            self.schedule_leave(Inactivity::Alone).await;
+        } else {
+            self.cancel_leave(Inactivity::Alone).await;
+            if self.auto_paused.swap(false, Ordering::SeqCst) {
+                let _ = self.resume().await;
+            }
+        }
+    }
+
+    /// Leave the voice channel after the idle timeout unless cancelled.
+    /// A timer that is already running is kept.
+    async fn schedule_leave(&self, reason: Inactivity) {
+        let mut timer = self.leave_timer.lock().await;
+        if timer.is_some() {
+            return;
+        }
+
+        let player = self.clone();
+        let handle = tokio::spawn(async move {
+            tokio::time::sleep(player.idle_timeout).await;
+            player.leave_inactive(reason).await;
+        });
+        *timer = Some(LeaveTimer { reason, handle });
+    }
+
+    /// Cancel the leave timer if it was started for the given reason
+    async fn cancel_leave(&self, reason: Inactivity) {
+        let mut timer = self.leave_timer.lock().await;
+        if timer
+            .as_ref()
+            .is_some_and(|pending| pending.reason == reason)
+        {
+            if let Some(pending) = timer.take() {
+                pending.handle.abort();
+            }
+        }
+    }
+
+    /// Leave the voice channel once the leave timer fires. The voice state
+    /// update for the disconnect then tears down the guild.
+    async fn leave_inactive(&self, reason: Inactivity) {
+        // This runs on the timer task itself, so only forget it
+        self.leave_timer.lock().await.take();
+
+        let why = match reason {
+            Inactivity::Idle => "nothing was playing",
+            Inactivity::Alone => "everyone left the voice channel",
+        };
+        println!("👋 Guild {}: Leaving voice because {}", self.guild_id, why);
+
+        self.stop().await;
+        let _ = self.manager.remove(self.guild_id).await;
+    }
+
+    /// Stop for good: cancel the leave timer and stop playback. Called by
+    /// `BotState::teardown_guild`.
+    pub async fn teardown(&self) {
+        if let Some(pending) = self.leave_timer.lock().await.take() {
+            pending.handle.abort();
+        }
+        self.auto_paused.store(false, Ordering::SeqCst);
+        self.stop().await;
+
+        // The current and fading tracks may never fire their end events once
+        // the call is gone, so give their cache references back now
+        let held: Vec<String> = self
+            .cache_refs
+            .lock()
+            .await
+            .drain()
+            .map(|(_, key)| key)
+            .collect();
+        for key in held {
+            self.cache.release(&key).await;
+        }
+    }
+}
@@ -0,0 +1,151 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+use tokio::sync::Mutex;
+
+use crate::cache::DownloadCache;
+use crate::music::{create_source, measure_loudness, ResolvedSource};
+use crate::queue::Queue;
+use crate::settings::Settings;
+
+/// How many upcoming queue entries to download while the current track plays
+const PREFETCH_AHEAD: usize = 2;
+
+/// Downloads the next few queue entries into the shared cache while the
+/// current track plays, so the track end handler can start the next song
+/// without waiting for yt-dlp.
+///
+/// Each prefetched source holds a cache reference so eviction leaves its file
+/// alone. The reference passes to the playing track once it is taken, and the
+/// track end handler releases it as usual.
+#[derive(Clone)]
+pub struct Prefetcher {
+    /// guild id -> track id -> downloaded source
+    ready: Arc<Mutex<HashMap<u64, HashMap<u64, ResolvedSource>>>>,
+    /// Track ids currently being downloaded
+    in_flight: Arc<Mutex<HashSet<u64>>>,
+    cache: DownloadCache,
+    settings: Settings,
+}
+
+impl Prefetcher {
+    pub fn new(cache: DownloadCache, settings: Settings) -> Self {
+        Self {
+            ready: Arc::new(Mutex::new(HashMap::new())),
+            in_flight: Arc::new(Mutex::new(HashSet::new())),
+            cache,
+            settings,
+        }
+    }
+
+    /// Start downloading the entries after the playing one (index 0).
+    /// Prefetched entries that are no longer upcoming are released.
+    pub async fn prefetch(&self, guild_id: u64, queue: &Queue) {
+        let upcoming: Vec<_> = queue
+            .lock()
+            .await
+            .iter()
+            .skip(1)
+            .take(PREFETCH_AHEAD)
+            .cloned()
+            .collect();
+
+        let upcoming_ids: HashSet<u64> = upcoming.iter().map(|t| t.id).collect();
+        self.retain(guild_id, &upcoming_ids).await;
+
+        for track in upcoming {
+            let already_ready = self
+                .ready
+                .lock()
+                .await
+                .get(&guild_id)
+                .is_some_and(|sources| sources.contains_key(&track.id));
+            if already_ready || !self.in_flight.lock().await.insert(track.id) {
+                continue;
+            }
+
+            let prefetcher = self.clone();
+            let queue = queue.clone();
+            tokio::spawn(async move {
+                let result = create_source(&prefetcher.cache, &track.query).await;
+                if let Some(key) = result.as_ref().ok().and_then(|r| r.cache_key.as_deref()) {
+                    prefetcher.measure(guild_id, key).await;
+                }
+                prefetcher.in_flight.lock().await.remove(&track.id);
+
+                let resolved = match result {
+                    Ok(resolved) => resolved,
+                    // Leave the entry alone, it is retried when it becomes the head
+                    Err(_) => return,
+                };
+
+                // The entry may have been skipped or removed while downloading
+                let still_queued = queue.lock().await.iter().skip(1).any(|t| t.id == track.id);
+                if still_queued {
+                    prefetcher
+                        .ready
+                        .lock()
+                        .await
+                        .entry(guild_id)
+                        .or_default()
+                        .insert(track.id, resolved);
+                } else {
+                    prefetcher.release(resolved).await;
+                }
+            });
+        }
+    }
+
+    /// Measure the loudness of a downloaded file now if the guild normalizes,
+    /// so the track doesn't wait for it when it starts
+    async fn measure(&self, guild_id: u64, key: &str) {
+        if !self.settings.get(guild_id).await.normalize {
+            return;
+        }
+        let measured = self
+            .cache
+            .loudness(key, |path| async move { measure_loudness(&path).await })
+            .await;
+        if let Err(e) = measured {
+            println!("⚠️ Prefetch: Couldn't measure loudness of {}: {}", key, e);
+        }
+    }
+
+    /// Take the prefetched source for a track, if its download finished
+    pub async fn take(&self, guild_id: u64, track_id: u64) -> Option<ResolvedSource> {
+        self.ready
+            .lock()
+            .await
+            .get_mut(&guild_id)
+            .and_then(|sources| sources.remove(&track_id))
+    }
+
+    /// Drop everything prefetched for a guild and release its files
+    pub async fn clear(&self, guild_id: u64) {
+        self.retain(guild_id, &HashSet::new()).await;
+    }
+
+    /// Keep only the prefetched sources for the given track ids
+    async fn retain(&self, guild_id: u64, keep: &HashSet<u64>) {
+        let dropped: Vec<ResolvedSource> = {
+            let mut ready = self.ready.lock().await;
+            let Some(sources) = ready.get_mut(&guild_id) else {
+                return;
+            };
+            let stale: Vec<u64> = sources.keys().filter(|id| !keep.contains(id)).copied().collect();
+            stale.iter().filter_map(|id| sources.remove(id)).collect()
+        };
+
+        for resolved in dropped {
+            self.release(resolved).await;
+        }
+    }
+
+    /// Give back the cache reference of a source that will not be played
+    async fn release(&self, resolved: ResolvedSource) {
+        if let Some(ref key) = resolved.cache_key {
+            self.cache.release(key).await;
+        }
+    }
+}
@@ -1,7 +1,212 @@
-use std::{collections::HashMap, sync::Arc};
+use serenity::model::id::{ChannelId, UserId};
+use songbird::input::AuxMetadata;
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, SystemTime},
+};
 use tokio::sync::Mutex;
 
+use crate::sources::Capabilities;
+
+/// A queued track. Starts out holding only the user's query and is filled in
+/// from its provider's metadata by the background `Resolver` or `create_source`.
+#[derive(Clone, Debug)]
+pub struct Track {
+    /// Unique id so background workers can find the entry after it moves
+    pub id: u64,
+    /// Search text or URL the user asked for
+    pub query: String,
+    pub title: Option<String>,
+    pub uploader: Option<String>,
+    pub duration: Option<Duration>,
+    pub source_url: Option<String>,
+    pub thumbnail: Option<String>,
+    /// Whether it can seek or is live, as reported by its provider
+    pub capabilities: Capabilities,
+    pub requester: UserId,
+    /// Text channel the track was requested from, for notices about it
+    pub channel_id: ChannelId,
+    pub enqueued_at: SystemTime,
+}
+
+impl Track {
+    pub fn new(query: String, requester: UserId, channel_id: ChannelId) -> Self {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+        Self {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            query,
+            title: None,
+            uploader: None,
+            duration: None,
+            source_url: None,
+            thumbnail: None,
+            capabilities: Capabilities::default(),
+            requester,
+            channel_id,
+            enqueued_at: SystemTime::now(),
+        }
+    }
+
+    /// Fill in fields from resolved metadata
+    pub fn apply_metadata(&mut self, metadata: &AuxMetadata, capabilities: Capabilities) {
+        self.capabilities = capabilities;
+        if let Some(title) = metadata.title.clone().or_else(|| metadata.track.clone()) {
+            self.title = Some(title);
+        }
+        if let Some(uploader) = metadata.channel.clone().or_else(|| metadata.artist.clone()) {
+            self.uploader = Some(uploader);
+        }
+        if metadata.duration.is_some() {
+            self.duration = metadata.duration;
+        }
+        if metadata.source_url.is_some() {
+            self.source_url = metadata.source_url.clone();
+        }
+        if metadata.thumbnail.is_some() {
+            self.thumbnail = metadata.thumbnail.clone();
+        }
+    }
+
+    /// Resolved title, or the raw query while the track is unresolved
+    pub fn display_title(&self) -> &str {
+        self.title.as_deref().unwrap_or(&self.query)
+    }
+
+    /// How long the track takes to play at `speed`
+    pub fn playback_duration(&self, speed: f64) -> Option<Duration> {
+        self.duration.map(|duration| duration.div_f64(speed))
+    }
+
+    /// One-line summary used by `!queue`, with the duration at `speed`
+    pub fn summary(&self, speed: f64) -> String {
+        let title = match &self.source_url {
+            Some(url) => format!("[{}](<{}>)", self.display_title(), url),
+            None => self.display_title().to_string(),
+        };
+
+        let mut line = format!("**{}**", title);
+        if let Some(uploader) = &self.uploader {
+            line.push_str(&format!(" by {}", uploader));
+        }
+        let length = if self.capabilities.live {
+            "LIVE".to_string()
+        } else {
+            format_optional_duration(self.playback_duration(speed))
+        };
+        line.push_str(&format!(" `[{}]`", length));
+        line.push_str(&format!(" • requested by <@{}>", self.requester));
+        if let Ok(since_epoch) = self.enqueued_at.duration_since(SystemTime::UNIX_EPOCH) {
+            line.push_str(&format!(" <t:{}:R>", since_epoch.as_secs()));
+        }
+        line
+    }
+}
+
+/// Format a duration as `m:ss` or `h:mm:ss`
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    let (hours, minutes, seconds) = (secs / 3600, (secs / 60) % 60, secs % 60);
+    if hours > 0 {
+        format!("{}:{:02}:{:02}", hours, minutes, seconds)
+    } else {
+        format!("{}:{:02}", minutes, seconds)
+    }
+}
+
+/// Parse a position given as `ss`, `m:ss` or `h:mm:ss`
+pub fn parse_duration(text: &str) -> Option<Duration> {
+    let parts: Vec<&str> = text.trim().split(':').collect();
+    if parts.len() > 3 {
+        return None;
+    }
+
+    let mut secs: u64 = 0;
+    for part in parts {
+        secs = secs.checked_mul(60)?.checked_add(part.parse().ok()?)?;
+    }
+    Some(Duration::from_secs(secs))
+}
+
+/// Format a duration that may not be known yet (unresolved or live tracks)
+pub fn format_optional_duration(duration: Option<Duration>) -> String {
+    duration
+        .map(format_duration)
+        .unwrap_or_else(|| "?:??".to_string())
+}
+
+/// Total duration of the given tracks and how many of them have no known duration
+pub fn total_duration<'a>(tracks: impl IntoIterator<Item = &'a Track>) -> (Duration, usize) {
+    tracks
+        .into_iter()
+        .fold((Duration::ZERO, 0), |(total, unknown), track| {
+            match track.duration {
+                Some(duration) => (total + duration, unknown),
+                None => (total, unknown + 1),
+            }
+        })
+}
+
+/// Shuffle tracks in place (Fisher-Yates). Randomness comes from the
+/// randomly seeded std hasher so no extra dependency is needed.
+pub fn shuffle(tracks: &mut [Track]) {
+    use std::collections::hash_map::RandomState;
+    use std::hash::{BuildHasher, Hasher};
+
+    let state = RandomState::new();
+    for i in (1..tracks.len()).rev() {
+        let mut hasher = state.build_hasher();
+        hasher.write_usize(i);
+        let j = (hasher.finish() % (i as u64 + 1)) as usize;
+        tracks.swap(i, j);
+    }
+}
+
+/// What happens to the finished entry when a track ends
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LoopMode {
+    /// Drop it and play the next entry
+    #[default]
+    Off,
+    /// Replay the same entry
+    Track,
+    /// Rotate it to the back of the queue
+    Queue,
+}
+
+impl LoopMode {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "off" | "none" | "disable" => Some(Self::Off),
+            "track" | "song" | "one" => Some(Self::Track),
+            "queue" | "all" => Some(Self::Queue),
+            _ => None,
+        }
+    }
+
+    /// Next mode when `!loop` is used without an argument
+    pub fn next(self) -> Self {
+        match self {
+            Self::Off => Self::Track,
+            Self::Track => Self::Queue,
+            Self::Queue => Self::Off,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Off => "off ➡️",
+            Self::Track => "track 🔂",
+            Self::Queue => "queue 🔁",
+        }
+    }
+}
+
 /// Queue type alias for easier imports
-pub type Queue = Arc<Mutex<Vec<String>>>;
+pub type Queue = Arc<Mutex<Vec<Track>>>;
 pub type QueueMap = Arc<Mutex<HashMap<u64, Queue>>>;
-
+pub type LoopModes = Arc<Mutex<HashMap<u64, LoopMode>>>;
@@ -0,0 +1,78 @@
+use serenity::http::Http;
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+
+use crate::music::resolve_metadata;
+use crate::queue::{Queue, Track};
+
+/// Maximum number of yt-dlp metadata lookups running at once across all guilds
+const MAX_CONCURRENT_LOOKUPS: usize = 4;
+
+/// Background worker pool that resolves queued tracks as soon as they are
+/// enqueued, so bad queries are reported immediately instead of failing
+/// silently when they reach the head of the queue
+#[derive(Clone)]
+pub struct Resolver {
+    permits: Arc<Semaphore>,
+}
+
+impl Resolver {
+    pub fn new() -> Self {
+        Self {
+            permits: Arc::new(Semaphore::new(MAX_CONCURRENT_LOOKUPS)),
+        }
+    }
+
+    /// Resolve metadata for a queued track in the background.
+    /// Tracks that fail to resolve are dropped and the requester is told why.
+    pub fn resolve(&self, http: Arc<Http>, queue: Queue, track: Track) {
+        let permits = self.permits.clone();
+
+        tokio::spawn(async move {
+            let _permit = match permits.acquire_owned().await {
+                Ok(permit) => permit,
+                Err(_) => return,
+            };
+
+            // The entry may have been removed (e.g. by !stop) while waiting for a permit
+            if !queue.lock().await.iter().any(|t| t.id == track.id) {
+                return;
+            }
+
+            match resolve_metadata(&track.query).await {
+                Ok(info) => {
+                    let mut queue = queue.lock().await;
+                    if let Some(entry) = queue.iter_mut().find(|t| t.id == track.id) {
+                        entry.apply_metadata(&info.metadata, info.capabilities);
+                    }
+                }
+                Err(e) => {
+                    // Never touch the playing entry at index 0, track end handling owns it
+                    let removed = {
+                        let mut queue = queue.lock().await;
+                        match queue.iter().position(|t| t.id == track.id) {
+                            Some(index) if index > 0 => {
+                                queue.remove(index);
+                                true
+                            }
+                            _ => false,
+                        }
+                    };
+
+                    if removed {
+                        let _ = track
+                            .channel_id
+                            .say(
+                                &http,
+                                format!(
+                                    "❌ <@{}> Couldn't find `{}`, removed it from the queue: {}",
+                                    track.requester, track.query, e
+                                ),
+                            )
+                            .await;
+                    }
+                }
+            }
+        });
+    }
+}
@@ -0,0 +1,150 @@
+use std::{collections::HashMap, path::PathBuf, sync::Arc};
+use tokio::sync::Mutex;
+
+/// Default for `SETTINGS_FILE`, relative to the working directory
+const DEFAULT_SETTINGS_FILE: &str = "data/guild_settings.txt";
+
+/// Highest volume in percent anyone can set, and the default per-guild maximum
+pub const VOLUME_LIMIT: u32 = 200;
+
+/// Longest crossfade between tracks in seconds
+pub const CROSSFADE_LIMIT: u32 = 12;
+
+/// Loudness target range in LUFS for normalization
+pub const LOUDNESS_TARGET_RANGE: std::ops::RangeInclusive<i32> = -30..=-5;
+
+/// Per-guild settings that survive restarts
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GuildSettings {
+    /// Volume in percent applied to every track
+    pub volume: u32,
+    /// Highest volume members may set, chosen by the server's admins
+    pub max_volume: u32,
+    /// Whether downloaded tracks are brought to `target_lufs`
+    pub normalize: bool,
+    /// Integrated loudness normalized tracks are adjusted to
+    pub target_lufs: i32,
+    /// Seconds consecutive tracks overlap while one fades into the other, 0 to cut
+    pub crossfade_secs: u32,
+}
+
+impl Default for GuildSettings {
+    fn default() -> Self {
+        Self {
+            volume: 100,
+            max_volume: VOLUME_LIMIT,
+            normalize: false,
+            target_lufs: -14,
+            crossfade_secs: 0,
+        }
+    }
+}
+
+impl GuildSettings {
+    /// Parse the `key=value` pairs of a settings line. Unknown keys and bad
+    /// values are ignored so older files keep loading.
+    fn parse<'a>(pairs: impl Iterator<Item = &'a str>) -> Self {
+        let mut settings = Self::default();
+        for pair in pairs {
+            let Some((key, value)) = pair.split_once('=') else {
+                continue;
+            };
+            match key {
+                "volume" => settings.volume = value.parse().unwrap_or(settings.volume),
+                "max_volume" => settings.max_volume = value.parse().unwrap_or(settings.max_volume),
+                "normalize" => settings.normalize = value.parse().unwrap_or(settings.normalize),
+                "target_lufs" => {
+                    settings.target_lufs = value.parse().unwrap_or(settings.target_lufs)
+                }
+                "crossfade_secs" => {
+                    settings.crossfade_secs = value.parse().unwrap_or(settings.crossfade_secs)
+                }
+                _ => {}
+            }
+        }
+        settings
+    }
+
+    fn serialize(&self) -> String {
+        format!(
+            "volume={} max_volume={} normalize={} target_lufs={} crossfade_secs={}",
+            self.volume, self.max_volume, self.normalize, self.target_lufs, self.crossfade_secs
+        )
+    }
+}
+
+/// Guild settings store, written to `SETTINGS_FILE` as one line per guild:
+/// `<guild id> key=value ...`
+#[derive(Clone)]
+pub struct Settings {
+    path: PathBuf,
+    guilds: Arc<Mutex<HashMap<u64, GuildSettings>>>,
+}
+
+impl Settings {
+    /// Load the settings file, starting empty if it doesn't exist yet
+    pub fn open() -> Self {
+        let path = PathBuf::from(
+            std::env::var("SETTINGS_FILE").unwrap_or_else(|_| DEFAULT_SETTINGS_FILE.to_string()),
+        );
+
+        let mut guilds = HashMap::new();
+        if let Ok(contents) = std::fs::read_to_string(&path) {
+            for line in contents.lines() {
+                let mut fields = line.split_whitespace();
+                let Some(guild_id) = fields.next().and_then(|id| id.parse().ok()) else {
+                    continue;
+                };
+                guilds.insert(guild_id, GuildSettings::parse(fields));
+            }
+        }
+        println!("⚙️ Loaded settings for {} guild(s)", guilds.len());
+
+        Self {
+            path,
+            guilds: Arc::new(Mutex::new(guilds)),
+        }
+    }
+
+    /// Settings of a guild, defaults if it never changed any
+    pub async fn get(&self, guild_id: u64) -> GuildSettings {
+        self.guilds
+            .lock()
+            .await
+            .get(&guild_id)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Change a guild's settings and save them. Returns the new settings.
+    pub async fn update(
+        &self,
+        guild_id: u64,
+        change: impl FnOnce(&mut GuildSettings),
+    ) -> GuildSettings {
+        let mut guilds = self.guilds.lock().await;
+        let settings = guilds.entry(guild_id).or_default();
+        change(settings);
+        let updated = *settings;
+
+        // Written while holding the lock so concurrent updates can't reorder
+        let contents: String = guilds
+            .iter()
+            .map(|(id, settings)| format!("{} {}\n", id, settings.serialize()))
+            .collect();
+        if let Err(e) = self.save(contents).await {
+            println!("⚠️ Failed to save guild settings: {}", e);
+        }
+        updated
+    }
+
+    /// Replace the settings file atomically
+    async fn save(&self, contents: String) -> std::io::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            tokio::fs::create_dir_all(dir).await?;
+        }
+        let temp = self.path.with_extension("tmp");
+        tokio::fs::write(&temp, contents).await?;
+        tokio::fs::rename(&temp, &self.path).await
+    }
+}
@@ -1,12 +1,12 @@
 use std::sync::Arc;
 use tokio::sync::Mutex;
 use tokio::signal::ctrl_c;
-use tokio::select;
 
 use crate::cleanup::cleanup_all_temp_files;
 use crate::queue::QueueMap;
 
 /// Shutdown handler for graceful termination
+#[derive(Clone)]
 pub struct ShutdownHandler {
     queues: QueueMap,
     is_shutting_down: Arc<Mutex<bool>>,
@@ -20,9 +20,9 @@ impl ShutdownHandler {
         }
     }
 
-    /// Check if shutdown is in progress
-    pub async fn is_shutting_down(&self) -> bool {
-        *self.is_shutting_down.lock().await
+    /// Shared shutdown flag, for tasks that need to watch it
+    pub fn flag(&self) -> Arc<Mutex<bool>> {
+        self.is_shutting_down.clone()
     }
 
     /// Wait for shutdown signal (SIGINT or SIGTERM)
@@ -40,9 +40,6 @@ impl ShutdownHandler {
 
     /// Perform graceful shutdown
     pub async fn shutdown(&self) {
-        // Set shutdown flag
-        *self.is_shutting_down.lock().await = true;
-
         println!("🧹 Cleaning up...");
 
         // Clear all queues
@@ -57,6 +54,9 @@ impl ShutdownHandler {
         cleanup_all_temp_files().await;
 
         println!("✅ Shutdown complete. Goodbye!");
+
+        // Set shutdown flag, main stops the client once it sees it
+        *self.is_shutting_down.lock().await = true;
     }
 
     /// Run the shutdown handler - waits for signal then shuts down
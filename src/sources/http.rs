@@ -0,0 +1,159 @@
+use serenity::async_trait;
+use songbird::input::{AuxMetadata, File, HttpRequest};
+use std::path::{Path, PathBuf};
+use tokio::io::AsyncWriteExt;
+
+use super::{url_host, Capabilities, SourceError, SourceInfo, SourceProvider};
+use crate::cache::{cache_key, DownloadCache};
+use crate::music::{ensure_decodable, probe_duration, ResolvedSource};
+
+/// File extensions recognised as direct links to audio
+const AUDIO_EXTENSIONS: &[&str] = &["mp3", "ogg", "oga", "opus", "flac", "wav", "m4a", "aac"];
+
+/// Links straight to an audio file. Files of known size are downloaded to the
+/// shared cache, anything else (e.g. internet radio) is streamed as live.
+pub struct DirectHttp;
+
+/// File name at the end of a URL's path, without the query string
+fn file_name(url: &str) -> Option<&str> {
+    let path = url.split(['?', '#']).next()?;
+    path.rsplit('/').next().filter(|name| !name.is_empty())
+}
+
+fn extension(url: &str) -> Option<String> {
+    let (_, ext) = file_name(url)?.rsplit_once('.')?;
+    let ext = ext.to_lowercase();
+    AUDIO_EXTENSIONS.contains(&ext.as_str()).then_some(ext)
+}
+
+impl DirectHttp {
+    /// Check the link with a HEAD request. Links without a length are treated
+    /// as endless streams.
+    async fn probe(&self, url: &str) -> Result<SourceInfo, SourceError> {
+        let client = reqwest::Client::new();
+        let response = match client
+            .head(url)
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+        {
+            Ok(response) => response,
+            // Some servers refuse HEAD (e.g. 405), only the headers of a GET are read then
+            Err(_) => client.get(url).send().await?.error_for_status()?,
+        };
+        let live = response.content_length().is_none();
+
+        let title = file_name(url).map(|name| name.replace("%20", " "));
+        Ok(SourceInfo {
+            metadata: AuxMetadata {
+                title,
+                source_url: Some(url.to_string()),
+                ..Default::default()
+            },
+            capabilities: Capabilities {
+                seekable: !live,
+                live,
+            },
+        })
+    }
+}
+
+#[async_trait]
+impl SourceProvider for DirectHttp {
+    fn name(&self) -> &'static str {
+        "HTTP"
+    }
+
+    fn matches(&self, query: &str) -> bool {
+        url_host(query).is_some() && extension(query).is_some()
+    }
+
+    async fn lookup(&self, query: &str) -> Result<SourceInfo, SourceError> {
+        self.probe(query).await
+    }
+
+    async fn create(
+        &self,
+        cache: &DownloadCache,
+        query: &str,
+    ) -> Result<ResolvedSource, SourceError> {
+        let SourceInfo {
+            mut metadata,
+            capabilities,
+        } = self.probe(query).await?;
+
+        if !capabilities.live {
+            let key = cache_key(query);
+            let download_key = key.clone();
+            let url = query.to_string();
+            let max_size = cache.quota_bytes();
+            match cache
+                .fetch(&key, |dir| async move {
+                    download_to(&dir, &download_key, &url, max_size).await
+                })
+                .await
+            {
+                Ok(path) => {
+                    metadata.duration = probe_duration(&path).await;
+                    return Ok(ResolvedSource {
+                        input: File::new(path.clone()).into(),
+                        cache_key: Some(key),
+                        path: Some(path),
+                        metadata,
+                        capabilities,
+                    });
+                }
+                Err(e) => eprintln!("⚠️ Download failed, streaming instead: {}", e),
+            }
+        }
+
+        Ok(ResolvedSource {
+            input: HttpRequest::new(reqwest::Client::new(), query.to_string()).into(),
+            cache_key: None,
+            path: None,
+            metadata,
+            capabilities,
+        })
+    }
+}
+
+/// Download a file into `dir` as `<key>.<ext>`, giving up on files larger than
+/// `max_size` bytes. It is written as `.part` first so a crash never leaves a
+/// truncated file that looks complete.
+async fn download_to(
+    dir: &Path,
+    key: &str,
+    url: &str,
+    max_size: u64,
+) -> Result<PathBuf, SourceError> {
+    let ext = extension(url).unwrap_or_else(|| "audio".to_string());
+    let path = dir.join(format!("{}.{}", key, ext));
+    let partial = dir.join(format!("{}.{}.part", key, ext));
+
+    let mut response = reqwest::get(url).await?.error_for_status()?;
+    if response
+        .content_length()
+        .is_some_and(|length| length > max_size)
+    {
+        return Err("The file is larger than the download cache".into());
+    }
+
+    // The reported length can be wrong, so count what actually arrives
+    let mut file = tokio::fs::File::create(&partial).await?;
+    let mut written: u64 = 0;
+    while let Some(chunk) = response.chunk().await? {
+        written += chunk.len() as u64;
+        if written > max_size {
+            drop(file);
+            let _ = tokio::fs::remove_file(&partial).await;
+            return Err("The file is larger than the download cache".into());
+        }
+        file.write_all(&chunk).await?;
+    }
+    file.flush().await?;
+    drop(file);
+
+    tokio::fs::rename(&partial, &path).await?;
+    ensure_decodable(&path).await?;
+    Ok(path)
+}
@@ -0,0 +1,100 @@
+use serenity::async_trait;
+use songbird::input::{AuxMetadata, File};
+use std::path::{Path, PathBuf};
+
+use super::{Capabilities, SourceError, SourceInfo, SourceProvider};
+use crate::cache::DownloadCache;
+use crate::music::{probe_duration, ResolvedSource};
+
+/// Files from the directory in `LOCAL_MUSIC_DIR`, picked with `local:<path>`.
+/// Disabled when it isn't set.
+pub struct LocalFiles {
+    dir: Option<PathBuf>,
+}
+
+impl LocalFiles {
+    pub fn from_env() -> Self {
+        Self {
+            dir: std::env::var("LOCAL_MUSIC_DIR")
+                .ok()
+                .filter(|dir| !dir.is_empty())
+                .and_then(|dir| PathBuf::from(dir).canonicalize().ok()),
+        }
+    }
+
+    /// Resolve a path relative to the music directory, refusing anything
+    /// outside it
+    fn resolve(&self, query: &str) -> Result<PathBuf, SourceError> {
+        let dir = self
+            .dir
+            .as_ref()
+            .ok_or("Local files are disabled, set LOCAL_MUSIC_DIR to enable them")?;
+        let path = dir
+            .join(query.trim_start_matches('/'))
+            .canonicalize()
+            .map_err(|_| format!("No local file `{}`", query))?;
+
+        if !path.starts_with(dir) || !path.is_file() {
+            return Err(format!("No local file `{}`", query).into());
+        }
+        Ok(path)
+    }
+
+    async fn info(&self, path: &Path) -> SourceInfo {
+        SourceInfo {
+            metadata: AuxMetadata {
+                title: path
+                    .file_stem()
+                    .map(|stem| stem.to_string_lossy().into_owned()),
+                duration: probe_duration(path).await,
+                ..Default::default()
+            },
+            capabilities: Capabilities {
+                seekable: true,
+                live: false,
+            },
+        }
+    }
+}
+
+#[async_trait]
+impl SourceProvider for LocalFiles {
+    fn name(&self) -> &'static str {
+        "Local files"
+    }
+
+    fn prefix(&self) -> Option<&'static str> {
+        Some("local:")
+    }
+
+    /// Only ever picked by prefix
+    fn matches(&self, _query: &str) -> bool {
+        false
+    }
+
+    async fn lookup(&self, query: &str) -> Result<SourceInfo, SourceError> {
+        let path = self.resolve(query)?;
+        Ok(self.info(&path).await)
+    }
+
+    /// Played in place, not copied to the cache
+    async fn create(
+        &self,
+        _cache: &DownloadCache,
+        query: &str,
+    ) -> Result<ResolvedSource, SourceError> {
+        let path = self.resolve(query)?;
+        let SourceInfo {
+            metadata,
+            capabilities,
+        } = self.info(&path).await;
+
+        Ok(ResolvedSource {
+            input: File::new(path.clone()).into(),
+            cache_key: None,
+            path: Some(path),
+            metadata,
+            capabilities,
+        })
+    }
+}
@@ -0,0 +1,134 @@
+mod http;
+mod local;
+mod ytdlp;
+
+use serenity::async_trait;
+use songbird::input::AuxMetadata;
+use std::sync::OnceLock;
+
+use crate::cache::DownloadCache;
+use crate::music::ResolvedSource;
+
+pub type SourceError = Box<dyn std::error::Error + Send + Sync>;
+
+/// What the player can do with a source
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Capabilities {
+    /// Whether `!seek` and friends work on it
+    pub seekable: bool,
+    /// Whether it is a stream without an end, such as a radio or a livestream
+    pub live: bool,
+}
+
+impl Default for Capabilities {
+    fn default() -> Self {
+        Self {
+            seekable: true,
+            live: false,
+        }
+    }
+}
+
+/// Metadata and capabilities a provider reports for a query
+pub struct SourceInfo {
+    pub metadata: AuxMetadata,
+    pub capabilities: Capabilities,
+}
+
+/// One track of a playlist, known only from flat extraction
+pub struct PlaylistEntry {
+    /// What to queue for it, usually its URL
+    pub query: String,
+    pub metadata: AuxMetadata,
+}
+
+/// Tracks listed by a playlist link
+pub struct Playlist {
+    pub title: Option<String>,
+    /// The first tracks, up to the limit that was asked for
+    pub entries: Vec<PlaylistEntry>,
+    /// Number of tracks in the whole playlist, if known
+    pub total: Option<usize>,
+}
+
+/// A place tracks can be played from
+#[async_trait]
+pub trait SourceProvider: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Prefix picking this provider explicitly, e.g. `sc:`
+    fn prefix(&self) -> Option<&'static str> {
+        None
+    }
+
+    /// Whether the provider handles a query given without a prefix
+    fn matches(&self, query: &str) -> bool;
+
+    /// List the first `limit` tracks if the query is a playlist link,
+    /// `None` for a single track
+    async fn playlist(&self, _query: &str, _limit: usize) -> Result<Option<Playlist>, SourceError> {
+        Ok(None)
+    }
+
+    /// Top `limit` results of a text search, for picking one
+    async fn search(&self, _query: &str, _limit: usize) -> Result<Vec<PlaylistEntry>, SourceError> {
+        Err(format!("{} can't be searched", self.name()).into())
+    }
+
+    /// Look up metadata without downloading anything
+    async fn lookup(&self, query: &str) -> Result<SourceInfo, SourceError>;
+
+    /// Create a playable source, from the shared cache where possible
+    async fn create(
+        &self,
+        cache: &DownloadCache,
+        query: &str,
+    ) -> Result<ResolvedSource, SourceError>;
+}
+
+/// Every provider, checked in order. YouTube comes first and is also the
+/// fallback for plain searches and URLs no other provider claims.
+fn providers() -> &'static [Box<dyn SourceProvider>] {
+    static PROVIDERS: OnceLock<Vec<Box<dyn SourceProvider>>> = OnceLock::new();
+    PROVIDERS.get_or_init(|| {
+        vec![
+            Box::new(ytdlp::YOUTUBE),
+            Box::new(ytdlp::SOUNDCLOUD),
+            Box::new(ytdlp::BANDCAMP),
+            Box::new(http::DirectHttp),
+            Box::new(local::LocalFiles::from_env()),
+        ]
+    })
+}
+
+/// Pick the provider for a query by its prefix or URL. Returns the provider
+/// and the query without the prefix.
+pub fn select(query: &str) -> (&'static dyn SourceProvider, &str) {
+    let query = query.trim();
+    for provider in providers() {
+        let prefixed = provider.prefix().and_then(|prefix| {
+            query
+                .get(..prefix.len())
+                .filter(|start| start.eq_ignore_ascii_case(prefix))
+                .map(|_| query[prefix.len()..].trim())
+        });
+        if let Some(rest) = prefixed {
+            return (provider.as_ref(), rest);
+        }
+    }
+
+    let provider = providers()
+        .iter()
+        .find(|provider| provider.matches(query))
+        .unwrap_or(&providers()[0]);
+    (provider.as_ref(), query)
+}
+
+/// Host of an http(s) URL, lowercased and without `www.`
+fn url_host(url: &str) -> Option<String> {
+    let rest = url
+        .strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"))?;
+    let host = rest.split(['/', '?', '#']).next()?.to_lowercase();
+    Some(host.strip_prefix("www.").unwrap_or(&host).to_string())
+}
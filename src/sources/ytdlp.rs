@@ -0,0 +1,305 @@
+use serenity::async_trait;
+use songbird::input::{AuxMetadata, Compose, File, YoutubeDl};
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use tokio::process::Command;
+
+use super::{
+    url_host, Capabilities, Playlist, PlaylistEntry, SourceError, SourceInfo, SourceProvider,
+};
+use crate::cache::{cache_key, DownloadCache};
+use crate::music::{ensure_decodable, ResolvedSource};
+
+pub const YOUTUBE: YtDlp = YtDlp {
+    name: "YouTube",
+    prefix: "yt:",
+    hosts: &["youtube.com", "youtu.be"],
+    playlist_paths: &["/playlist"],
+    playlist_params: &["list"],
+    search: Some("ytsearch"),
+};
+
+pub const SOUNDCLOUD: YtDlp = YtDlp {
+    name: "SoundCloud",
+    prefix: "sc:",
+    hosts: &["soundcloud.com"],
+    playlist_paths: &["/sets/"],
+    playlist_params: &[],
+    search: Some("scsearch"),
+};
+
+pub const BANDCAMP: YtDlp = YtDlp {
+    name: "Bandcamp",
+    prefix: "bc:",
+    hosts: &["bandcamp.com"],
+    playlist_paths: &["/album/"],
+    playlist_params: &[],
+    search: None,
+};
+
+/// A site played through yt-dlp. Downloads go to the shared cache, with
+/// streaming as the fallback.
+pub struct YtDlp {
+    name: &'static str,
+    prefix: &'static str,
+    /// Hosts of the site's URLs, subdomains included
+    hosts: &'static [&'static str],
+    /// Parts of URL paths that mark a playlist or album
+    playlist_paths: &'static [&'static str],
+    /// Query parameters naming a playlist. Links like `watch?v=…&list=…` name
+    /// a video too, they are queued as the whole playlist like yt-dlp does.
+    playlist_params: &'static [&'static str],
+    /// yt-dlp search scheme for plain queries, `None` if the site can't be searched
+    search: Option<&'static str>,
+}
+
+impl YtDlp {
+    /// What yt-dlp is asked for: URLs as they are, anything else as a
+    /// search for the first hit
+    fn target(&self, query: &str) -> Result<String, SourceError> {
+        if query.starts_with("http") {
+            return Ok(query.to_string());
+        }
+        self.search_for(query, 1)
+    }
+
+    /// yt-dlp URL searching the site for the top `count` hits
+    fn search_for(&self, query: &str, count: usize) -> Result<String, SourceError> {
+        match self.search {
+            Some(search) => Ok(format!("{}{}:{}", search, count, query)),
+            None => Err(format!("{} can't be searched, use a link", self.name).into()),
+        }
+    }
+
+    fn is_playlist(&self, url: &str) -> bool {
+        self.matches(url)
+            && (self.playlist_paths.iter().any(|path| url.contains(path))
+                || self
+                    .playlist_params
+                    .iter()
+                    .any(|name| query_param(url, name).is_some_and(|value| !value.is_empty())))
+    }
+
+    fn source(&self, query: &str) -> Result<YoutubeDl, SourceError> {
+        Ok(YoutubeDl::new(reqwest::Client::new(), self.target(query)?))
+    }
+}
+
+/// Value of a URL's query parameter, `None` if it has none by that name
+fn query_param<'a>(url: &'a str, name: &str) -> Option<&'a str> {
+    let (_, query) = url.split('#').next()?.split_once('?')?;
+    query
+        .split('&')
+        .find_map(|pair| match pair.split_once('=') {
+            Some((key, value)) if key == name => Some(value),
+            _ => None,
+        })
+}
+
+/// yt-dlp reports no duration for livestreams
+fn capabilities(metadata: &AuxMetadata) -> Capabilities {
+    let live = metadata.duration.is_none();
+    Capabilities {
+        seekable: !live,
+        live,
+    }
+}
+
+#[async_trait]
+impl SourceProvider for YtDlp {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn prefix(&self) -> Option<&'static str> {
+        Some(self.prefix)
+    }
+
+    fn matches(&self, query: &str) -> bool {
+        url_host(query).is_some_and(|host| {
+            self.hosts
+                .iter()
+                .any(|known| host == *known || host.ends_with(&format!(".{}", known)))
+        })
+    }
+
+    async fn playlist(&self, query: &str, limit: usize) -> Result<Option<Playlist>, SourceError> {
+        if !self.is_playlist(query) {
+            return Ok(None);
+        }
+        let playlist = list_playlist(query, limit).await?;
+        if playlist.entries.is_empty() {
+            return Err("The playlist is empty or private".into());
+        }
+        Ok(Some(playlist))
+    }
+
+    async fn search(&self, query: &str, limit: usize) -> Result<Vec<PlaylistEntry>, SourceError> {
+        // Search results list like a playlist
+        let search = self.search_for(query, limit)?;
+        Ok(list_playlist(&search, limit).await?.entries)
+    }
+
+    async fn lookup(&self, query: &str) -> Result<SourceInfo, SourceError> {
+        let metadata = self.source(query)?.aux_metadata().await?;
+        Ok(SourceInfo {
+            capabilities: capabilities(&metadata),
+            metadata,
+        })
+    }
+
+    async fn create(
+        &self,
+        cache: &DownloadCache,
+        query: &str,
+    ) -> Result<ResolvedSource, SourceError> {
+        let mut source = self.source(query)?;
+
+        // Resolve title, duration, thumbnail etc. for the queue entry
+        let metadata = source.aux_metadata().await?;
+        let capabilities = capabilities(&metadata);
+
+        // Play the resolved video (not the search query, which could match
+        // something else by now) from the shared cache, downloading it on a
+        // miss. Livestreams never finish downloading, those are streamed.
+        if let Some(url) = metadata.source_url.clone().filter(|_| !capabilities.live) {
+            let key = cache_key(&url);
+            let download_key = key.clone();
+            let max_size = cache.quota_bytes();
+            match cache
+                .fetch(&key, |dir| async move {
+                    download_to(&dir, &download_key, &url, max_size).await
+                })
+                .await
+            {
+                Ok(path) => {
+                    return Ok(ResolvedSource {
+                        input: File::new(path.clone()).into(),
+                        cache_key: Some(key),
+                        path: Some(path),
+                        metadata,
+                        capabilities,
+                    });
+                }
+                Err(e) => eprintln!("⚠️ Download failed, streaming instead: {}", e),
+            }
+        }
+
+        // Stream directly, nothing is written to disk
+        Ok(ResolvedSource {
+            input: source.into(),
+            cache_key: None,
+            path: None,
+            metadata,
+            capabilities,
+        })
+    }
+}
+
+/// List a playlist's first `limit` tracks with yt-dlp's flat extraction,
+/// which reads only the playlist page and not every track
+async fn list_playlist(url: &str, limit: usize) -> Result<Playlist, SourceError> {
+    let output = Command::new("yt-dlp")
+        .args(["--flat-playlist", "--no-warnings", "--playlist-end"])
+        .arg(limit.to_string())
+        .args(["--print", "%(playlist_title)s\t%(playlist_count)s\t%(url)s\t%(title)s\t%(duration)s\t%(channel,uploader)s"])
+        .arg(url)
+        .output()
+        .await?;
+
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr)
+            .trim()
+            .to_string()
+            .into());
+    }
+
+    // One line per track, yt-dlp prints `NA` for missing fields
+    let field = |value: &str| Some(value.to_string()).filter(|v| v != "NA" && !v.is_empty());
+    let mut playlist = Playlist {
+        title: None,
+        entries: Vec::new(),
+        total: None,
+    };
+    let printed = String::from_utf8_lossy(&output.stdout);
+    for line in printed.lines() {
+        let fields: Vec<&str> = line.split('\t').collect();
+        let [title, count, url, track_title, duration, uploader] = fields[..] else {
+            continue;
+        };
+        let Some(url) = field(url) else { continue };
+
+        playlist.title = playlist.title.or_else(|| field(title));
+        playlist.total = playlist.total.or_else(|| count.parse().ok());
+        // The total still counts them, they just can't be queued
+        if is_unavailable(track_title) {
+            continue;
+        }
+        playlist.entries.push(PlaylistEntry {
+            query: url.clone(),
+            metadata: AuxMetadata {
+                title: field(track_title),
+                channel: field(uploader),
+                duration: duration
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|secs| secs.is_finite() && *secs > 0.0)
+                    .map(Duration::from_secs_f64),
+                source_url: Some(url),
+                ..Default::default()
+            },
+        });
+    }
+    Ok(playlist)
+}
+
+/// Flat extraction lists private and deleted videos with a placeholder title
+/// such as `[Private video]` instead of leaving them out
+fn is_unavailable(title: &str) -> bool {
+    matches!(
+        title,
+        "[Private video]" | "[Deleted video]" | "[Unavailable video]"
+    )
+}
+
+/// Download a track with yt-dlp into `dir` as `<key>.<ext>` and return the
+/// exact file path. yt-dlp skips files larger than `max_size` bytes.
+async fn download_to(
+    dir: &Path,
+    key: &str,
+    url: &str,
+    max_size: u64,
+) -> Result<PathBuf, SourceError> {
+    let template = dir.join(format!("{}.%(ext)s", key));
+
+    let output = Command::new("yt-dlp")
+        .args(["-f", "bestaudio/best", "--no-playlist", "--no-simulate"])
+        .arg("--max-filesize")
+        .arg(max_size.to_string())
+        .args(["--print", "after_move:filepath", "-o"])
+        .arg(&template)
+        .arg(url)
+        .output()
+        .await?;
+
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr)
+            .trim()
+            .to_string()
+            .into());
+    }
+
+    // yt-dlp prints the final path once the file has been written
+    let printed = String::from_utf8_lossy(&output.stdout);
+    let path = printed
+        .lines()
+        .last()
+        .map(|line| PathBuf::from(line.trim()))
+        .filter(|path| path.starts_with(dir) && path.is_file())
+        .ok_or("yt-dlp did not report the downloaded file")?;
+
+    ensure_decodable(&path).await?;
+    Ok(path)
+}
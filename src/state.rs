@@ -0,0 +1,104 @@
+use serenity::{http::Http, model::id::GuildId};
+use songbird::Songbird;
+use std::{collections::HashMap, sync::Arc};
+use tokio::sync::Mutex;
+
+use crate::cache::DownloadCache;
+use crate::player::{Player, PlayerMap};
+use crate::prefetch::Prefetcher;
+use crate::queue::{LoopModes, Queue, QueueMap};
+use crate::resolver::Resolver;
+use crate::settings::Settings;
+use crate::shutdown::ShutdownHandler;
+
+/// Default for `PLAYLIST_MAX_TRACKS`
+const DEFAULT_PLAYLIST_MAX_TRACKS: usize = 100;
+
+/// Shared bot state handed to every command
+#[derive(Clone)]
+pub struct BotState {
+    pub queues: QueueMap,
+    pub loop_modes: LoopModes,
+    pub players: PlayerMap,
+    pub cache: DownloadCache,
+    pub resolver: Resolver,
+    pub prefetcher: Prefetcher,
+    pub settings: Settings,
+    /// Graceful shutdown, triggered by Ctrl+C or `!shutdown`
+    pub shutdown: ShutdownHandler,
+    /// Most tracks queued from one playlist, from `PLAYLIST_MAX_TRACKS`
+    pub playlist_limit: usize,
+}
+
+impl BotState {
+    pub fn new() -> Self {
+        let cache = DownloadCache::open();
+        let settings = Settings::open();
+        let queues: QueueMap = Arc::new(Mutex::new(HashMap::new()));
+
+        Self {
+            queues: queues.clone(),
+            loop_modes: Arc::new(Mutex::new(HashMap::new())),
+            players: Arc::new(Mutex::new(HashMap::new())),
+            cache: cache.clone(),
+            resolver: Resolver::new(),
+            prefetcher: Prefetcher::new(cache, settings.clone()),
+            settings,
+            shutdown: ShutdownHandler::new(queues),
+            playlist_limit: std::env::var("PLAYLIST_MAX_TRACKS")
+                .ok()
+                .and_then(|value| value.parse().ok())
+                .filter(|limit| *limit > 0)
+                .unwrap_or(DEFAULT_PLAYLIST_MAX_TRACKS),
+        }
+    }
+
+    pub async fn get_or_create_queue(&self, guild_id: u64) -> Queue {
+        let mut queues = self.queues.lock().await;
+        queues
+            .entry(guild_id)
+            .or_insert_with(|| Arc::new(Mutex::new(Vec::new())))
+            .clone()
+    }
+
+    /// Playback controller for a guild, created on first use
+    pub async fn get_or_create_player(
+        &self,
+        manager: Arc<Songbird>,
+        http: Arc<Http>,
+        guild_id: GuildId,
+    ) -> Player {
+        let queue = self.get_or_create_queue(guild_id.get()).await;
+        let mut players = self.players.lock().await;
+        players
+            .entry(guild_id.get())
+            .or_insert_with(|| Player::new(guild_id, manager, http, queue, self))
+            .clone()
+    }
+
+    /// Tear down everything held for a guild: stop playback, release its
+    /// cached and prefetched files, drop its queue, loop mode and player and
+    /// leave voice. Used by `!leave`, when the bot is removed from a guild and
+    /// when it is disconnected from voice. Safe to call more than once.
+    pub async fn teardown_guild(&self, manager: Arc<Songbird>, guild_id: GuildId) {
+        let player = self.players.lock().await.remove(&guild_id.get());
+        let queue = self.queues.lock().await.remove(&guild_id.get());
+        self.loop_modes.lock().await.remove(&guild_id.get());
+        let had_state = player.is_some() || queue.is_some();
+
+        if let Some(player) = player {
+            player.teardown().await;
+        }
+        if let Some(queue) = queue {
+            queue.lock().await.clear();
+        }
+        self.prefetcher.clear(guild_id.get()).await;
+
+        if manager.get(guild_id).is_some() {
+            let _ = manager.remove(guild_id).await;
+        }
+        if had_state {
+            println!("🧹 Guild {}: Cleaned up guild state", guild_id);
+        }
+    }
+}